@@ -0,0 +1,1332 @@
+//! Transformation of the fenced code blocks in a readme
+
+use std::{borrow::Cow, collections::BTreeMap, fmt, iter, ops::Range, sync::OnceLock};
+
+use regex::Regex;
+
+use crate::{
+	diagnostics::Diagnostics,
+	links,
+	markdown::{self, FencedBlock},
+	options::Options,
+	report::{BlockStatus, Report},
+};
+
+/// Directive that can be placed on its own line in a code block to leave it untouched
+pub(crate) const SKIP_DIRECTIVE: &str = "// docify:skip";
+
+/// Directive that can be placed on its own line in a code block to run it in a function returning an `Option`,
+/// rather than adding the usual `Result` trailer
+pub(crate) const OPTION_DIRECTIVE: &str = "// docify:option";
+
+/// Directive that can be placed on the first line of a code block, followed by an edition (e.g. `2015`), to set the
+/// edition the block is tested with
+pub(crate) const EDITION_DIRECTIVE: &str = "// docify:edition";
+
+/// Attribute that can be added to a code block's info string to hide all of its lines from rendered docs, while still
+/// running it as a doctest
+pub(crate) const HIDDEN_ATTR: &str = "docify(hidden)";
+
+/// Attribute that can be added to a code block's info string to keep whitespace-affecting transformations of the whole
+/// readme (like [dedenting](Options::dedent)) from changing the block
+pub(crate) const VERBATIM_ATTR: &str = "docify(verbatim)";
+
+/// Placeholder for a block's body in the [template](Options::template)
+pub(crate) const BODY_PLACEHOLDER: &str = "{{body}}";
+
+/// Hidden lines placed before the body of blocks with the [`OPTION_DIRECTIVE`]
+const OPTION_PREFIX: &str = "# fn main() { fn _doctest() -> Option<()> {";
+
+/// Hidden lines placed after the body of blocks with the [`OPTION_DIRECTIVE`]
+const OPTION_SUFFIX: [&str; 2] = ["# Some(())", "# } _doctest().unwrap(); }"];
+
+/// Hidden lines placed before the body of blocks that end with an `Ok(...)` expression without a type annotation
+const RESULT_PREFIX: &str = "# fn _doctest() -> Result<(), Box<dyn std::error::Error>> {";
+
+/// Hidden lines placed after the body of blocks that end with an `Ok(...)` expression without a type annotation
+const RESULT_SUFFIX: [&str; 2] = ["# }", "# _doctest().unwrap();"];
+
+/// Closed fenced code block found in a readme, with its info string broken down
+pub(crate) struct CodeBlock<'a> {
+	/// Byte range of the whole block (from the opening fence to the end of the closing fence) in the readme
+	pub(crate) range: Range<usize>,
+
+	/// Text preceding the opening fence on its line, which lines added to the block are prefixed with
+	pub(crate) indent: &'a str,
+
+	/// Opening fence
+	pub(crate) fence: &'a str,
+
+	/// Full info string following the opening fence
+	pub(crate) info: &'a str,
+
+	/// Language token of the info string
+	pub(crate) lang: &'a str,
+
+	/// Attributes following the language in the info string
+	pub(crate) attrs: Vec<&'a str>,
+
+	/// Newline sequence following the opening fence
+	pub(crate) open_newline: &'a str,
+
+	/// Contents of the block, excluding the newline preceding the closing fence (and the edition directive)
+	pub(crate) body: &'a str,
+
+	/// Edition given by the block's [`EDITION_DIRECTIVE`], if it has one
+	pub(crate) edition: Option<&'a str>,
+
+	/// Feature given by the block's `docify(feature = "...")` attribute, if it has one
+	pub(crate) feature: Option<&'a str>,
+
+	/// Newline sequence preceding the closing fence
+	pub(crate) newline: &'a str,
+
+	/// Closing fence, including its indentation
+	pub(crate) close: &'a str,
+}
+
+impl<'a> CodeBlock<'a> {
+	/// Builds a code block from a fenced block, if it's closed
+	fn from_fenced(block: FencedBlock<'a>) -> Option<Self> {
+		let mut tokens = block.info_tokens();
+
+		// Take the edition directive out of the body if it's on the first line
+		let first_line = markdown::lines(block.body).next();
+		let edition = first_line.as_ref().and_then(|line| {
+			let directive = line.content.trim_start_matches([' ', '\t', '>']).trim_end();
+			let edition = directive.strip_prefix(EDITION_DIRECTIVE)?.trim();
+			(!edition.is_empty() && edition.chars().all(|c| c.is_ascii_digit())).then_some(edition)
+		});
+		let body = match (&edition, &first_line) {
+			(Some(_), Some(line)) => block.body.get(line.next_start()..).unwrap_or_default(),
+			_ => block.body,
+		};
+
+		let feature = feature_attr_regex()
+			.captures(block.info)
+			.and_then(|caps| caps.get(1))
+			.map(|feature| feature.as_str());
+
+		Some(Self {
+			range: block.range,
+			indent: block.indent,
+			fence: block.fence,
+			info: block.info,
+			lang: tokens.next().unwrap_or(""),
+			attrs: tokens.collect(),
+			open_newline: block.open_newline,
+			body,
+			edition,
+			feature,
+			newline: if block.newline.is_empty() {
+				block.open_newline
+			} else {
+				block.newline
+			},
+			close: block.close?,
+		})
+	}
+
+	/// Gets the block's info string without any attributes rustdoc doesn't understand, with its attributes separated by
+	/// commas only, and with the edition attribute for its [`EDITION_DIRECTIVE`] if it has one
+	fn base_info(&self) -> Cow<'a, str> {
+		let info = strip_custom_attrs(self.info);
+		let info = if info.contains([' ', '\t']) {
+			Cow::Owned(
+				info.split([',', ' ', '\t'])
+					.filter(|token| !token.is_empty())
+					.collect::<Vec<_>>()
+					.join(","),
+			)
+		} else {
+			info
+		};
+		match self.edition {
+			Some(edition) => Cow::Owned(format!("{info},edition{edition}")),
+			None => info,
+		}
+	}
+
+	/// Gets the block's last non-blank line of code (hidden or not), without any hiding prefix or surrounding
+	/// whitespace
+	fn last_code_line(&self) -> Option<&'a str> {
+		markdown::lines(self.body)
+			.map(|line| line.content.trim())
+			.filter(|line| !line.is_empty())
+			.last()
+			.map(|line| line.strip_prefix("# ").unwrap_or(line).trim_start())
+	}
+
+	/// Checks whether the block's last line is an `Ok::<...>(...)` expression, which makes rustdoc treat the block as
+	/// returning a `Result` already
+	fn returns_ok(&self) -> bool {
+		self.last_code_line()
+			.is_some_and(|line| line.starts_with("Ok::<") && line.ends_with(')'))
+	}
+
+	/// Checks whether the block's last line is an `Ok(...)` expression without a type annotation, which rustdoc also
+	/// treats as the return value, but can't infer the error type of
+	fn returns_bare_ok(&self) -> bool {
+		self.is_rust()
+			&& self
+				.last_code_line()
+				.is_some_and(|line| line.starts_with("Ok(") && line.ends_with(')'))
+	}
+
+	/// Checks whether rustdoc treats the block as Rust code: when its language is Rust, or when its info string has
+	/// nothing but rustdoc's own attributes (like `no_run`), if anything
+	fn is_rust(&self) -> bool {
+		matches!(self.lang.to_lowercase().as_str(), "rust" | "rs")
+			|| strip_custom_attrs(self.info)
+				.split([',', ' ', '\t'])
+				.filter(|token| !token.is_empty())
+				.all(|token| token == "rust" || is_rustdoc_attr(token))
+	}
+
+	/// Checks whether rustdoc will run (or at least compile) the block as a doctest
+	fn is_doctest(&self) -> bool {
+		self.is_rust()
+			&& !iter::once(&self.lang)
+				.chain(&self.attrs)
+				.any(|attr| attr.starts_with("ignore"))
+	}
+
+	/// Determines why the block (with the given 1-based index) shouldn't receive a trailer, if there's a reason not to
+	pub(crate) fn skip_reason(&self, index: usize, opts: &Options) -> Option<SkipReason> {
+		if opts.skip_blocks.contains(&index) {
+			Some(SkipReason::Excluded)
+		} else if opts.skip_blockquoted_code && self.indent.contains('>') {
+			Some(SkipReason::Blockquoted)
+		} else if self.has_directive(SKIP_DIRECTIVE) {
+			Some(SkipReason::Directive)
+		} else if let Some(attr) = self.attrs.iter().find(|attr| attr.starts_with("ignore")) {
+			Some(SkipReason::Ignored((*attr).to_owned()))
+		} else if !self.is_rust() {
+			// The remaining reasons are about how rustdoc runs Rust code, so other languages always get their trailer
+			None
+		} else if main_regex().is_match(self.body) {
+			Some(SkipReason::HasMain)
+		} else if self.returns_ok() {
+			Some(SkipReason::ReturnsOk)
+		} else if self.returns_bare_ok() {
+			None
+		} else if !question_mark_regex().is_match(self.body) {
+			Some(SkipReason::NoQuestionMark)
+		} else {
+			None
+		}
+	}
+
+	/// Wraps the given body in a hidden block with a `#[cfg(feature = "...")]` attribute if the block has a feature
+	/// attribute, so that its code is only compiled with the feature enabled. Bodies with their own `main` function are
+	/// left as-is, since rustdoc wouldn't find it inside the block.
+	fn gate<'b>(&self, body: &'b str) -> Cow<'b, str> {
+		let Some(feature) = self.feature.filter(|_| !main_regex().is_match(body)) else {
+			return Cow::Borrowed(body);
+		};
+
+		let (indent, newline) = (self.indent, self.newline);
+		Cow::Owned(format!(
+			"{indent}# #[cfg(feature = \"{feature}\")]{newline}{indent}# {{{newline}{body}{newline}{indent}# }}"
+		))
+	}
+
+	/// Checks whether the block has a line consisting of just the given directive
+	fn has_directive(&self, directive: &str) -> bool {
+		markdown::lines(self.body).any(|line| is_directive(line.content, directive))
+	}
+
+	/// Renders the block with the [`OPTION_DIRECTIVE`], wrapping its body in a function returning an `Option`, with the
+	/// given (already hidden) prelude lines placed before it
+	fn render_option(&self, info: &str, prelude: &str) -> String {
+		let (indent, newline) = (self.indent, self.newline);
+		let body = strip_directive(self.body, OPTION_DIRECTIVE);
+		let body = self.gate(&body);
+		let [some, close] = OPTION_SUFFIX;
+		self.render(
+			info,
+			&format!("{prelude}{indent}{OPTION_PREFIX}{newline}{body}{newline}{indent}{some}{newline}{indent}{close}"),
+			None,
+		)
+	}
+
+	/// Renders a block that was skipped for the given reason, without a trailer, with the given (already hidden)
+	/// prelude lines placed before its body if it was only skipped because it doesn't use the question mark operator
+	fn render_skipped(&self, info: &str, reason: &SkipReason, prelude: &str) -> String {
+		match reason {
+			SkipReason::Directive => self.render(info, &self.gate(&strip_directive(self.body, SKIP_DIRECTIVE)), None),
+			SkipReason::NoQuestionMark => self.render(info, &self.gate(&format!("{prelude}{}", self.body)), None),
+			// Gating a block that ends with `Ok::<...>(...)` would keep rustdoc from treating it as the return value
+			SkipReason::ReturnsOk => self.render(info, self.body, None),
+			_ => self.render(info, &self.gate(self.body), None),
+		}
+	}
+
+	/// Renders a block that ends with an `Ok(...)` expression without a type annotation, wrapping its body in a
+	/// function returning a `Result` with a boxed error so the expression's type can be inferred, with the given
+	/// (already hidden) prelude lines placed before it
+	fn render_result(&self, info: &str, prelude: &str) -> String {
+		let (indent, newline) = (self.indent, self.newline);
+		let [close, call] = RESULT_SUFFIX;
+		let inner = format!(
+			"{indent}{RESULT_PREFIX}{newline}{}{newline}{indent}{close}{newline}{indent}{call}",
+			self.body
+		);
+		self.render(
+			info,
+			&format!(
+				"{prelude}{indent}# fn main() {{{newline}{}{newline}{indent}# }}",
+				self.gate(&inner)
+			),
+			None,
+		)
+	}
+
+	/// Renders the block with the given info string and body, wrapped in the [template](Options::template) if there is
+	/// one, or with the trailer for its language appended otherwise
+	fn render_wrapped(&self, info: &str, body: &str, opts: &Options) -> String {
+		let Some(template) = &opts.template else {
+			return self.render(info, body, trailer_for(self.lang, opts));
+		};
+
+		// Every line of the template other than the one containing the body needs the block's indentation
+		let wrapped = markdown::lines(template)
+			.map(|line| {
+				if line.content.contains(BODY_PLACEHOLDER) {
+					line.content.replace(BODY_PLACEHOLDER, body)
+				} else {
+					format!("{}{}", self.indent, line.content)
+				}
+			})
+			.collect::<Vec<_>>()
+			.join(self.newline);
+		self.render(info, &wrapped, None)
+	}
+
+	/// Renders the block back to markdown with the given info string and body, and the given trailer appended
+	/// (separated by a blank line), or with all lines hidden if the block has the [`HIDDEN_ATTR`]
+	fn render(&self, info: &str, body: &str, trailer: Option<&str>) -> String {
+		let (indent, fence, open_newline, newline, close) =
+			(self.indent, self.fence, self.open_newline, self.newline, self.close);
+		if body.is_empty() {
+			return format!("{fence}{info}{open_newline}{close}");
+		}
+
+		// Hidden blocks don't get the blank line before the trailer, since it would be the only visible line
+		if self.attrs.contains(&HIDDEN_ATTR) {
+			let body = hide_lines(body, newline, indent);
+			return match trailer {
+				Some(trailer) => format!("{fence}{info}{open_newline}{body}{newline}{indent}{trailer}{newline}{close}"),
+				None => format!("{fence}{info}{open_newline}{body}{newline}{close}"),
+			};
+		}
+
+		match trailer {
+			Some(trailer) => format!(
+				"{fence}{info}{open_newline}{body}{newline}{}{newline}{indent}{trailer}{newline}{close}",
+				indent.trim_end()
+			),
+			None => format!("{fence}{info}{open_newline}{body}{newline}{close}"),
+		}
+	}
+}
+
+/// Reason a code block was left without a trailer
+pub(crate) enum SkipReason {
+	/// The block's index is in [`Options::skip_blocks`]
+	Excluded,
+
+	/// The block is inside a blockquote and [`Options::skip_blockquoted_code`] is set
+	Blockquoted,
+
+	/// The block contains the [`SKIP_DIRECTIVE`]
+	Directive,
+
+	/// The block has an attribute that keeps rustdoc from running it
+	Ignored(String),
+
+	/// The block defines its own `main` function
+	HasMain,
+
+	/// The block already ends with an `Ok(...)` expression
+	ReturnsOk,
+
+	/// The block doesn't use the question mark operator, so it doesn't need a trailer
+	NoQuestionMark,
+}
+
+impl fmt::Display for SkipReason {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			Self::Excluded => write!(f, "its index is listed in `skip_blocks`"),
+			Self::Blockquoted => write!(f, "is inside a blockquote"),
+			Self::Directive => write!(f, "contains a `{SKIP_DIRECTIVE}` directive"),
+			Self::Ignored(attr) => write!(f, "has the `{attr}` attribute"),
+			Self::HasMain => write!(f, "defines its own `fn main`"),
+			Self::ReturnsOk => write!(f, "already ends with an `Ok::<...>(...)` expression"),
+			Self::NoQuestionMark => write!(f, "doesn't use the `?` operator"),
+		}
+	}
+}
+
+/// Gets the trailer to append to code blocks of the given fence language, if it has one
+pub(crate) fn trailer_for<'opts>(lang: &str, opts: &'opts Options) -> Option<&'opts str> {
+	opts.trailers.get(&trailer_key(lang)).map(String::as_str)
+}
+
+/// Gets the key the trailer for the given fence language is stored under in [`Options::trailers`]: the lowercased
+/// language, with `rs` treated as an alias of `rust`
+pub(crate) fn trailer_key(lang: &str) -> String {
+	match lang.to_lowercase().as_str() {
+		"rs" => "rust".to_owned(),
+		lang => lang.to_owned(),
+	}
+}
+
+/// Appends the configured trailer (`# Ok::<(), Box<dyn std::error::Error>>(())` for Rust by default) to the end of
+/// all code blocks with a language that has one, unless there's a reason to skip the block
+pub(crate) fn add_trailers(readme: &str, opts: &Options, diagnostics: &mut Diagnostics, report: &mut Report) -> String {
+	if opts.trailers.is_empty() {
+		return readme.to_owned();
+	}
+
+	let blocks = find_blocks(readme, opts);
+	let infos = blocks.iter().map(CodeBlock::base_info).collect::<Vec<_>>();
+	let imports = item_imports(readme, opts, diagnostics);
+	let mut rendered = Vec::with_capacity(blocks.len());
+	let mut groups: Vec<Vec<usize>> = Vec::new();
+
+	for (idx, block) in blocks.iter().enumerate() {
+		let index = idx.saturating_add(1);
+		let reason = block.skip_reason(index, opts);
+		let info = &infos[idx];
+
+		// Hold on to blocks that are to be merged, since they're rendered all together at the end
+		let mergeable = matches!(reason, None | Some(SkipReason::NoQuestionMark))
+			&& block.is_doctest()
+			&& !block.has_directive(OPTION_DIRECTIVE)
+			&& !block.returns_bare_ok();
+		if mergeable && (opts.merge_blocks || opts.merge_adjacent_blocks) {
+			// Add the block to the current group if merging everything, or if only blank lines separate it from the
+			// group's last block
+			let continues = groups.last().and_then(|group| group.last()).is_some_and(|&prev| {
+				opts.merge_blocks
+					|| (prev.saturating_add(1) == idx
+						&& readme[blocks[prev].range.end..block.range.start].trim().is_empty())
+			});
+			match groups.last_mut() {
+				Some(group) if continues => group.push(idx),
+				_ => groups.push(vec![idx]),
+			}
+			rendered.push(String::new());
+			continue;
+		}
+
+		let prelude = prelude_lines(block, block.body, &imports, opts);
+		rendered.push(match reason {
+			Some(reason) => {
+				diagnostics.note(format!("code block {index} (`{}`) skipped: {reason}", block.info));
+				report.block(index, block.info, BlockStatus::Skipped(reason.to_string()));
+				block.render_skipped(info, &reason, &prelude)
+			}
+			None if block.has_directive(OPTION_DIRECTIVE) => {
+				report.block(index, block.info, BlockStatus::OptionWrapper);
+				block.render_option(info, &prelude)
+			}
+			None if block.returns_bare_ok() => {
+				report.block(index, block.info, BlockStatus::ResultWrapper);
+				block.render_result(info, &prelude)
+			}
+			None => {
+				report.block(index, block.info, BlockStatus::Trailer);
+				let body = format!("{prelude}{}", block.body);
+				block.render_wrapped(info, &block.gate(&body), opts)
+			}
+		});
+	}
+
+	// Combine each group of merged blocks into a single doctest in place of its last block, with the earlier blocks'
+	// code hidden, and mark the earlier blocks as ignored so they don't run on their own
+	for group in &groups {
+		let Some((&last, earlier)) = group.split_last() else {
+			continue;
+		};
+		let last_block = &blocks[last];
+		let code = group.iter().map(|&idx| blocks[idx].body).collect::<Vec<_>>().join("\n");
+		let mut body = prelude_lines(last_block, &code, &imports, opts);
+		for &idx in earlier {
+			let block = &blocks[idx];
+			body.push_str(&hide_lines(block.body, last_block.newline, last_block.indent));
+			body.push_str(last_block.newline);
+			rendered[idx] = block.render(&format!("{},ignore", infos[idx]), block.body, None);
+			diagnostics.note(format!(
+				"code block {} (`{}`) merged into code block {}",
+				idx.saturating_add(1),
+				block.info,
+				last.saturating_add(1)
+			));
+			report.block(
+				idx.saturating_add(1),
+				block.info,
+				BlockStatus::MergedInto(last.saturating_add(1)),
+			);
+		}
+		body.push_str(last_block.body);
+
+		let uses_question_mark = group
+			.iter()
+			.any(|&idx| question_mark_regex().is_match(blocks[idx].body));
+		let body = last_block.gate(&body);
+		rendered[last] = if uses_question_mark {
+			last_block.render_wrapped(&infos[last], &body, opts)
+		} else {
+			last_block.render(&infos[last], &body, None)
+		};
+		report.block(last.saturating_add(1), last_block.info, BlockStatus::MergeTarget);
+	}
+
+	// Splice the rendered blocks into the readme
+	let mut output = String::with_capacity(readme.len());
+	let mut end = 0;
+	for (block, rendered) in blocks.iter().zip(rendered) {
+		output.push_str(&readme[end..block.range.start]);
+		output.push_str(&rendered);
+		end = block.range.end;
+	}
+	output.push_str(&readme[end..]);
+	output
+}
+
+/// Finds the [known items](Options::known_items) that reference links in the readme refer to, to import from the crate
+/// given by [`Options::import_items`] in the code blocks that use them, as pairs of the item's name and the path to
+/// import. Items whose names are shared by other referenced items are left out with a warning, since it's unclear
+/// which of them a block would mean.
+fn item_imports(readme: &str, opts: &Options, diagnostics: &mut Diagnostics) -> Vec<(String, String)> {
+	let (Some(krate), Some(known_items)) = (&opts.import_items, &opts.known_items) else {
+		return Vec::new();
+	};
+
+	let mut by_name: BTreeMap<&str, Vec<&str>> = BTreeMap::new();
+	for item in links::referenced_items(readme) {
+		if known_items.contains(item) {
+			let name = item.rsplit("::").next().unwrap_or(item);
+			by_name.entry(name).or_default().push(item);
+		}
+	}
+
+	by_name
+		.into_iter()
+		.filter_map(|(name, items)| {
+			if let [item] = items.as_slice() {
+				return Some((name.to_owned(), format!("{krate}::{item}")));
+			}
+
+			let items = items.iter().map(|item| format!("`{item}`")).collect::<Vec<_>>();
+			diagnostics.warn(format!(
+				"`{name}` isn't imported in code blocks, since it could refer to any of {}",
+				items.join(", ")
+			));
+			None
+		})
+		.collect()
+}
+
+/// Builds the hidden lines to place at the start of a block's body (each followed by the block's newline): `use`
+/// declarations for the given item imports that the block's code refers to without importing or defining them, then
+/// the [prelude](Options::prelude), preceding each of its `use`s with a hidden `#[allow(unused_imports)]` if
+/// [`Options::allow_unused_prelude`] is set
+fn prelude_lines(block: &CodeBlock<'_>, code: &str, imports: &[(String, String)], opts: &Options) -> String {
+	let (indent, newline) = (block.indent, block.newline);
+	let mut lines = String::new();
+
+	let prelude = opts.prelude.as_deref().unwrap_or_default();
+	for (name, path) in imports {
+		let name = regex::escape(name);
+		let used = Regex::new(&format!(r"\b{name}\b")).expect("unable to build item usage regex");
+		let declared = Regex::new(&format!(
+			r"\b(?:use\b[^;]*|(?:struct|enum|union|trait|type|fn|mod|const|static)\s+){name}\b"
+		))
+		.expect("unable to build item declaration regex");
+		if used.is_match(code) && !declared.is_match(code) && !declared.is_match(prelude) {
+			lines.extend([indent, "# use ", path, ";", newline]);
+		}
+	}
+
+	for line in markdown::lines(prelude) {
+		let code = line.content.trim();
+		if code.is_empty() {
+			continue;
+		}
+
+		if opts.allow_unused_prelude && code.starts_with("use ") {
+			lines.extend([indent, "# #[allow(unused_imports)]", newline]);
+		}
+		lines.extend([indent, "# ", code, newline]);
+	}
+	lines
+}
+
+/// Removes the lines hidden from rendered docs (`# `-prefixed ones) from all code blocks with a language that has a
+/// trailer, along with any blank lines left at the end of them, and removes blocks with the [`HIDDEN_ATTR`] entirely
+pub(crate) fn strip_hidden_lines(readme: &str, opts: &Options) -> String {
+	let mut output = String::with_capacity(readme.len());
+	let mut end = 0;
+	for block in find_blocks(readme, opts) {
+		output.push_str(&readme[end..block.range.start]);
+		end = block.range.end;
+
+		if block.attrs.contains(&VERBATIM_ATTR) {
+			output.push_str(&block.render(&block.base_info(), block.body, None));
+			continue;
+		}
+		if block.attrs.contains(&HIDDEN_ATTR) {
+			let rest = &readme[end..];
+			end = end.saturating_add(markdown::lines(rest).next().map_or(0, |line| line.next_start()));
+			continue;
+		}
+
+		let body = markdown::lines(block.body)
+			.filter_map(|line| {
+				let content = line.content.strip_prefix(block.indent).unwrap_or(line.content);
+				let (leading, code) = content.split_at(content.len().saturating_sub(content.trim_start().len()));
+				if code == "#" || code.starts_with("# ") {
+					None
+				} else if let Some(code) = code.strip_prefix("##") {
+					// Rustdoc shows lines starting with `##` with the first `#` removed
+					Some([block.indent, leading, "#", code, line.newline].concat())
+				} else {
+					Some([line.content, line.newline].concat())
+				}
+			})
+			.collect::<String>();
+		output.push_str(&block.render(&block.base_info(), body.trim_end(), None));
+	}
+	output.push_str(&readme[end..]);
+	output
+}
+
+/// Finds the byte ranges of the code blocks in the readme with the [`VERBATIM_ATTR`], from the start of the opening
+/// fence's line to the end of the closing fence
+pub(crate) fn verbatim_ranges(readme: &str) -> Vec<Range<usize>> {
+	markdown::fenced_blocks(readme)
+		.into_iter()
+		.filter_map(CodeBlock::from_fenced)
+		.filter(|block| block.attrs.contains(&VERBATIM_ATTR))
+		.map(|block| block.range.start.saturating_sub(block.indent.len())..block.range.end)
+		.collect()
+}
+
+/// Counts the Rust code blocks in the readme that rustdoc will run (or at least compile) as doctests
+pub(crate) fn count_doctests(readme: &str) -> usize {
+	markdown::fenced_blocks(readme)
+		.into_iter()
+		.filter_map(CodeBlock::from_fenced)
+		.filter(CodeBlock::is_doctest)
+		.count()
+}
+
+/// Notes every distinct fence language in the readme, along with how many of its blocks rustdoc will run (or at least
+/// compile) as doctests, so that typos in languages and untested examples are easy to spot
+pub(crate) fn note_languages(readme: &str, diagnostics: &mut Diagnostics) {
+	let mut langs: BTreeMap<&str, (usize, usize)> = BTreeMap::new();
+	for block in markdown::fenced_blocks(readme)
+		.into_iter()
+		.filter_map(CodeBlock::from_fenced)
+	{
+		let (blocks, doctests) = langs.entry(block.lang).or_default();
+		*blocks = blocks.saturating_add(1);
+		if block.is_doctest() {
+			*doctests = doctests.saturating_add(1);
+		}
+	}
+	if langs.is_empty() {
+		return;
+	}
+
+	let langs = langs
+		.into_iter()
+		.map(|(lang, (blocks, doctests))| {
+			let lang = if lang.is_empty() {
+				"(none)".to_owned()
+			} else {
+				format!("`{lang}`")
+			};
+			let plural = if blocks == 1 { "" } else { "s" };
+			match doctests {
+				0 => format!("{lang} ({blocks} block{plural}, not run as doctests)"),
+				_ => format!("{lang} ({blocks} block{plural}, {doctests} run as doctests)"),
+			}
+		})
+		.collect::<Vec<_>>();
+	diagnostics.note(format!("code block languages: {}", langs.join(", ")));
+}
+
+/// Turns each Rust code block in the readme that rustdoc will run (or at least compile) as a doctest into the source of
+/// a standalone example program, the same way rustdoc does: hidden lines are revealed, and blocks without their own
+/// `main` function are wrapped in one (returning the block's result if it ends with `(())`)
+pub(crate) fn scraped_examples(readme: &str) -> Vec<String> {
+	markdown::fenced_blocks(readme)
+		.into_iter()
+		.filter_map(CodeBlock::from_fenced)
+		.filter(CodeBlock::is_doctest)
+		.map(|block| {
+			let body = markdown::lines(block.body)
+				.map(|line| {
+					let content = line.content.strip_prefix(block.indent).unwrap_or(line.content);
+					let (leading, code) = content.split_at(content.len().saturating_sub(content.trim_start().len()));
+					let code = if code == "#" {
+						""
+					} else {
+						code.strip_prefix("# ")
+							.or_else(|| code.strip_prefix('#').filter(|code| code.starts_with('#')))
+							.unwrap_or(code)
+					};
+					[leading, code, "\n"].concat()
+				})
+				.collect::<String>();
+			let body = body.trim_end();
+
+			if main_regex().is_match(body) {
+				format!("{body}\n")
+			} else if body.ends_with("(())") {
+				let inner = "fn _inner() -> Result<(), impl core::fmt::Debug>";
+				format!("fn main() {{ {inner} {{\n{body}\n}} _inner().unwrap() }}\n")
+			} else {
+				format!("fn main() {{\n{body}\n}}\n")
+			}
+		})
+		.collect()
+}
+
+/// Moves every top-level Rust code block in the readme into an `Examples` section at the end, each under a subheading
+/// with the text of the nearest preceding heading (or `Example N`, where `N` is the block's index among the moved
+/// blocks, if there is none). Blocks nested in list items or blockquotes are left where they are, since they'd lose
+/// their meaning out of context. The blank line following each moved block is removed along with it.
+pub(crate) fn examples_as_sections(readme: &str) -> String {
+	let headings = markdown::headings(readme);
+	let blocks = markdown::fenced_blocks(readme)
+		.into_iter()
+		.filter_map(CodeBlock::from_fenced)
+		.filter(|block| block.is_rust() && block.indent.is_empty())
+		.collect::<Vec<_>>();
+	if blocks.is_empty() {
+		return readme.to_owned();
+	}
+
+	let newline = markdown::lines(readme).next().map_or("\n", |line| line.newline);
+	let newline = if newline.is_empty() { "\n" } else { newline };
+	let skip_newline = |pos: usize| {
+		let rest = &readme[pos..];
+		let len = if rest.starts_with("\r\n") {
+			2
+		} else {
+			usize::from(rest.starts_with(['\n', '\r']))
+		};
+		pos.saturating_add(len)
+	};
+
+	let mut output = String::with_capacity(readme.len());
+	let mut sections = String::new();
+	let mut last = 0;
+	for (idx, block) in blocks.iter().enumerate() {
+		output.push_str(&readme[last..block.range.start]);
+
+		// Skip the block's line break, along with the following blank line if the block was preceded by one
+		last = skip_newline(block.range.end);
+		let before = output.trim_end_matches([' ', '\t']);
+		if before.is_empty() || before.ends_with("\n\n") || before.ends_with("\r\n\r\n") || before.ends_with("\r\r") {
+			last = skip_newline(last);
+		}
+
+		let title = headings
+			.iter()
+			.rev()
+			.find(|heading| heading.range.end <= block.range.start && !heading.text.is_empty())
+			.map_or_else(
+				|| format!("Example {}", idx.saturating_add(1)),
+				|heading| heading.text.to_owned(),
+			);
+		sections.extend([
+			newline,
+			"## ",
+			&title,
+			newline,
+			newline,
+			&readme[block.range.clone()],
+			newline,
+		]);
+	}
+	output.push_str(&readme[last..]);
+
+	let mut output = output.trim_end().to_owned();
+	if !output.is_empty() {
+		output.extend([newline, newline]);
+	}
+	output.extend(["# Examples", newline]);
+	output.push_str(&sections);
+	output
+}
+
+/// Warns about lines inside code blocks that look like the block's closing fence, except that they have an info string
+/// (e.g. ```` ```rs ```` in a ```` ```rust ```` block), since such lines don't close the block
+pub(crate) fn check_closing_fences(readme: &str, diagnostics: &mut Diagnostics) {
+	let line_number = |pos: usize| readme[..pos].matches('\n').count().saturating_add(1);
+
+	for block in markdown::fenced_blocks(readme) {
+		let Some(marker) = block.fence.chars().next() else {
+			continue;
+		};
+
+		for line in markdown::lines(block.body) {
+			let rest = line.content.trim_start_matches([' ', '\t', '>']);
+			let info = rest.trim_start_matches(marker);
+			let fence_len = rest.len().saturating_sub(info.len());
+			if fence_len < block.fence.len() || info.trim().is_empty() {
+				continue;
+			}
+
+			diagnostics.warn(format!(
+				"line {} looks like the closing fence of the code block opened on line {}, but it has an info string \
+				 (`{}`), so it doesn't close the block",
+				line_number(block.body_range.start.saturating_add(line.start)),
+				line_number(block.range.start),
+				info.trim(),
+			));
+		}
+	}
+}
+
+/// Finds all closed code blocks in the readme with a language that has a trailer
+fn find_blocks<'a>(readme: &'a str, opts: &Options) -> Vec<CodeBlock<'a>> {
+	markdown::fenced_blocks(readme)
+		.into_iter()
+		.filter_map(CodeBlock::from_fenced)
+		.filter(|block| trailer_for(block.lang, opts).is_some())
+		.collect()
+}
+
+/// Removes all `docify(...)` attributes and line highlighting hints (e.g. `{1,3}`), along with their preceding
+/// separators, from an info string, since rustdoc doesn't understand them
+fn strip_custom_attrs(info: &str) -> Cow<'_, str> {
+	static RE: OnceLock<Regex> = OnceLock::new();
+	RE.get_or_init(|| {
+		Regex::new(r"[, \t]*(?:\bdocify\([^)]*\)|\{[^}]*\})").expect("unable to build custom attribute regex")
+	})
+	.replace_all(info, "")
+}
+
+/// Hides all lines of a code block body from rendered docs by prefixing them with `# ` (after the indentation), leaving
+/// already-hidden lines as they are
+fn hide_lines(body: &str, newline: &str, indent: &str) -> String {
+	markdown::lines(body)
+		.map(|line| {
+			let content = line.content.strip_prefix(indent).unwrap_or(line.content);
+			match content.trim_end() {
+				"" | "#" => format!("{indent}#"),
+				_ if content.starts_with("# ") => line.content.to_owned(),
+				_ => format!("{indent}# {content}"),
+			}
+		})
+		.collect::<Vec<_>>()
+		.join(newline)
+}
+
+/// Checks whether an info string token is one of the attributes rustdoc understands for Rust code blocks
+fn is_rustdoc_attr(token: &str) -> bool {
+	matches!(
+		token,
+		"ignore" | "should_panic" | "no_run" | "compile_fail" | "test_harness" | "standalone_crate"
+	) || token.starts_with("ignore-")
+		|| token.starts_with("edition")
+		|| token
+			.strip_prefix('E')
+			.is_some_and(|code| code.len() == 4 && code.chars().all(|c| c.is_ascii_digit()))
+}
+
+/// Checks whether a line of a code block consists of just the given directive
+fn is_directive(line: &str, directive: &str) -> bool {
+	line.trim_start_matches([' ', '\t', '>']).trim_end() == directive
+}
+
+/// Removes the lines consisting of just the given directive from a code block body
+fn strip_directive(body: &str, directive: &str) -> String {
+	markdown::lines(body)
+		.filter(|line| !is_directive(line.content, directive))
+		.map(|line| [line.content, line.newline].concat())
+		.collect::<String>()
+		.trim_end_matches(['\r', '\n'])
+		.to_owned()
+}
+
+/// Gets the regex for detecting a `main` function definition, which is only built once since it's used for every block
+fn main_regex() -> &'static Regex {
+	static RE: OnceLock<Regex> = OnceLock::new();
+	RE.get_or_init(|| Regex::new(r"\bfn\s+main\s*\(").expect("unable to build main regex"))
+}
+
+/// Gets the regex for finding a `docify(feature = "...")` attribute in an info string, which is only built once
+fn feature_attr_regex() -> &'static Regex {
+	static RE: OnceLock<Regex> = OnceLock::new();
+	RE.get_or_init(|| {
+		Regex::new(r#"\bdocify\(\s*feature\s*=\s*"([^"]*)"\s*\)"#).expect("unable to build feature attribute regex")
+	})
+}
+
+/// Gets the regex for detecting usage of the question mark operator, which is only built once
+fn question_mark_regex() -> &'static Regex {
+	static RE: OnceLock<Regex> = OnceLock::new();
+	RE.get_or_init(|| Regex::new(r"[\w)\]]\?").expect("unable to build question mark regex"))
+}
+
+#[cfg(test)]
+mod tests {
+	//! Tests for the code block transformations
+
+	use std::{
+		fmt::Write,
+		time::{Duration, Instant},
+	};
+
+	use regex::RegexBuilder;
+
+	use super::*;
+	use crate::{diagnostics::Level, options::Input};
+
+	/// Transforms a readme with [`add_trailers`], with the given options
+	fn trailers(readme: &str, opts: &Options) -> String {
+		add_trailers(readme, opts, &mut Diagnostics::new(false), &mut Report::default())
+	}
+
+	/// Parses the given `name = value` options the way the macro would
+	fn options(options: &str) -> Options {
+		syn::parse_str::<Input>(&format!(
+			r#""README.md", "https://docs.rs/some_crate/", "./", {options}"#
+		))
+		.expect("options should parse")
+		.options
+	}
+
+	/// Rust blocks that use the question mark operator get the trailer, others are left as they are
+	#[test]
+	fn add_trailers_skips_blocks() {
+		assert_eq!(
+			trailers(include_str!("../tests/fixtures/skipped.md"), &Options::default()),
+			"```rust,ignore\nnot_checked()?;\n```\n\n```rust\nfn main() {}\n```\n\n```rust\nsomething();\n```\n\n\
+			 ```rust\nsomething()?;\n```\n\n```rust,no_run\nsomething()?;\n\n\
+			 # Ok::<(), Box<dyn std::error::Error>>(())\n```\n"
+		);
+	}
+
+	/// Blocks ending with a turbofished `Ok` are left alone, and ones ending with a bare `Ok(())` get a hidden function
+	/// with a concrete error type
+	#[test]
+	fn add_trailers_wraps_bare_ok() {
+		assert_eq!(
+			trailers(include_str!("../tests/fixtures/returns_ok.md"), &Options::default()),
+			"```rust\nlet value: i32 = \"42\".parse()?;\nOk::<(), std::num::ParseIntError>(())\n```\n\n```rust\
+			 \n# fn main() {\n# fn _doctest() -> Result<(), Box<dyn std::error::Error>> {\n\
+			 let value: i32 = \"42\".parse()?;\n\
+			 assert_eq!(value, 42);\nOk(())\n# }\n# _doctest().unwrap();\n# }\n```\n"
+		);
+	}
+
+	/// The `docify:option` directive runs the block in a function returning `Option<()>` instead
+	#[test]
+	fn add_trailers_wraps_option_blocks() {
+		assert_eq!(
+			trailers(include_str!("../tests/fixtures/option.md"), &Options::default()),
+			"```rust\n# fn main() { fn _doctest() -> Option<()> {\nlet first = [1, 2, 3].first()?;\n\
+			 assert_eq!(*first, 1);\n# Some(())\n# } _doctest().unwrap(); }\n```\n"
+		);
+	}
+
+	/// Every line of a `docify(hidden)` block is hidden, and the attribute is removed
+	#[test]
+	fn add_trailers_hides_hidden_blocks() {
+		assert_eq!(
+			trailers(include_str!("../tests/fixtures/hidden.md"), &Options::default()),
+			"```rust\n# let value = \"42\".parse::<i32>()?;\n#\n# assert_eq!(value, 42);\n\
+			 # Ok::<(), Box<dyn std::error::Error>>(())\n```\n"
+		);
+	}
+
+	/// Feature-gated blocks are wrapped in a hidden `#[cfg]` block
+	#[test]
+	fn add_trailers_gates_features() {
+		assert_eq!(
+			trailers(include_str!("../tests/fixtures/feature_gated.md"), &Options::default()),
+			"```rust\n# #[cfg(feature = \"serde\")]\n# {\nlet value: i32 = \"1\".parse()?;\nassert_eq!(value, 1);\
+			 \n# }\n\n# Ok::<(), Box<dyn std::error::Error>>(())\n```\n"
+		);
+	}
+
+	/// The `docify:edition` directive becomes an `edition` attribute
+	#[test]
+	fn add_trailers_sets_editions() {
+		assert_eq!(
+			trailers(include_str!("../tests/fixtures/editions.md"), &Options::default()),
+			"```rust,edition2015\nlet async = 1;\nassert_eq!(async, 1);\n```\n\n\
+			 ```rust,edition2021\nlet r#async = 1;\nassert_eq!(r#async, 1);\n```\n"
+		);
+	}
+
+	/// Info strings are normalized to comma-separated attributes, without highlighting hints
+	#[test]
+	fn add_trailers_normalizes_info_strings() {
+		let block = "```rust,no_run\nsomething()?;\n\n# Ok::<(), Box<dyn std::error::Error>>(())\n```\n";
+		assert_eq!(
+			trailers(include_str!("../tests/fixtures/separators.md"), &Options::default()),
+			[block, block, block].join("\n")
+		);
+		assert_eq!(
+			trailers(include_str!("../tests/fixtures/highlight.md"), &Options::default()),
+			"```rust\nlet a = 1;\nlet b: i32 = \"2\".parse()?;\n\n# Ok::<(), Box<dyn std::error::Error>>(())\n```\n"
+		);
+	}
+
+	/// Generates a readme with the given number of sections, each with a Rust block that needs a trailer
+	fn large_readme(steps: usize) -> String {
+		let mut readme = String::new();
+		for step in 1..=steps {
+			let _ = write!(readme, "## Step {step}\n\n```rust\nstep({step})?;\n```\n\n");
+		}
+		readme
+	}
+
+	/// Appends trailers the way the single regex used before the line-oriented scanner did
+	fn regex_trailers(readme: &str) -> String {
+		RegexBuilder::new(r"```(rust|rs)(\r\n|\r|\n)(.+?)(\r\n|\r|\n)```")
+			.dot_matches_new_line(true)
+			.case_insensitive(true)
+			.build()
+			.expect("unable to build old trailer regex")
+			.replace_all(readme, "```$1$2$3$4$4# Ok::<(), Box<dyn std::error::Error>>(())$4```")
+			.into_owned()
+	}
+
+	/// Large readmes are handled in one pass, and blocks that are never closed are left alone
+	#[test]
+	fn add_trailers_handles_many_blocks() {
+		let docs = trailers(
+			&format!("{}```rust\nnever_closed()?;\n", large_readme(1000)),
+			&Options::default(),
+		);
+		let trailer = "\n\n# Ok::<(), Box<dyn std::error::Error>>(())\n```";
+		assert_eq!(docs.matches(trailer).count(), 1000);
+		assert!(docs.contains(&format!("## Step 1000\n\n```rust\nstep(1000)?;{trailer}")));
+		assert!(docs.ends_with("```rust\nnever_closed()?;\n"));
+	}
+
+	/// Readmes the old regex handled correctly get the same output
+	#[test]
+	fn add_trailers_matches_regex_output() {
+		let readme = large_readme(1000);
+		assert_eq!(trailers(&readme, &Options::default()), regex_trailers(&readme));
+	}
+
+	/// The time taken grows about linearly with the size of the readme: eight times the blocks take nowhere near the
+	/// 64 times as long that quadratic growth would
+	#[test]
+	fn add_trailers_scales_linearly() {
+		let time = |readme: &str| {
+			(0..3)
+				.map(|_| {
+					let start = Instant::now();
+					trailers(readme, &Options::default());
+					start.elapsed()
+				})
+				.min()
+				.unwrap_or(Duration::ZERO)
+		};
+		let (small, large) = (time(&large_readme(500)), time(&large_readme(4000)));
+		assert!(
+			large < small * 24,
+			"500 blocks took {small:?}, while 4000 took {large:?}"
+		);
+	}
+
+	/// A fence with an info string doesn't close a block, and gets a warning
+	#[test]
+	fn check_closing_fences_warns_about_info_strings() {
+		let readme = include_str!("../tests/fixtures/fence_info.md");
+		assert_eq!(trailers(readme, &Options::default()), readme);
+
+		let mut diagnostics = Diagnostics::new(false);
+		check_closing_fences(readme, &mut diagnostics);
+		assert!(matches!(diagnostics.messages(), [(Level::Warning, message)] if message.contains("line 3")));
+	}
+
+	/// The `trailers` option replaces the Rust trailer
+	#[test]
+	fn add_trailers_uses_given_trailers() {
+		assert_eq!(
+			trailers(
+				include_str!("../tests/fixtures/trailers.md"),
+				&options(r##"trailers = [("rust", "# Ok::<(), &str>(())")]"##)
+			),
+			"```rust\nlet x: Option<u8> = None;\nx.ok_or(\"nope\")?;\n\n# Ok::<(), &str>(())\n```\n"
+		);
+	}
+
+	/// The `template` option wraps the body instead, and must contain the placeholder exactly once
+	#[test]
+	fn add_trailers_uses_template() {
+		assert_eq!(
+			trailers(
+				include_str!("../tests/fixtures/template.md"),
+				&options(
+					r##"template = "# fn main() -> Result<(), std::num::ParseIntError> {\n{{body}}\n# Ok(())\n# }""##
+				)
+			),
+			"```rust\n# fn main() -> Result<(), std::num::ParseIntError> {\nlet number: u8 = \"42\".parse()?;\
+			 \n# Ok(())\n# }\n```\n"
+		);
+
+		for template in ["# fn main() {}", "{{body}}\\n{{body}}"] {
+			let input = format!(r#""README.md", "https://docs.rs/some_crate/", "./", template = "{template}""#);
+			assert!(syn::parse_str::<Input>(&input).is_err());
+		}
+	}
+
+	/// The `skip_blocks` option skips blocks by their 1-based index
+	#[test]
+	fn add_trailers_skips_listed_blocks() {
+		let trailer = "\n\n# Ok::<(), Box<dyn std::error::Error>>(())\n";
+		assert_eq!(
+			trailers(
+				include_str!("../tests/fixtures/numbered.md"),
+				&options("skip_blocks = [2, 4]")
+			),
+			format!(
+				"```rust\none()?;{trailer}```\n\n```rust\ntwo()?;\n```\n\n```rust\nthree()?;{trailer}```\n\n\
+				 ```rust\nfour()?;\n```\n\n```rust\nfive()?;{trailer}```\n"
+			)
+		);
+	}
+
+	/// The `skip_blockquoted_code` option skips blocks inside blockquotes
+	#[test]
+	fn add_trailers_skips_blockquoted_blocks() {
+		assert_eq!(
+			trailers(
+				include_str!("../tests/fixtures/blockquoted.md"),
+				&options("skip_blockquoted_code = true")
+			),
+			"> Previously, this took a path:\n>\n> ```rust\n> let file = open(\"path\")?;\n> ```\n\n\
+			 Now it takes a reader:\n\n\
+			 ```rust\nlet file = open(reader)?;\n\n# Ok::<(), Box<dyn std::error::Error>>(())\n```\n"
+		);
+	}
+
+	/// The `merge_blocks` option combines every block into the last one
+	#[test]
+	fn add_trailers_merges_blocks() {
+		assert_eq!(
+			trailers(
+				include_str!("../tests/fixtures/merged.md"),
+				&options("merge_blocks = true")
+			),
+			"```rust,ignore\nlet number: u8 = \"42\".parse()?;\n```\n\nLater on:\n\n```rust\n\
+			 # let number: u8 = \"42\".parse()?;\nassert_eq!(number, 42);\n\n\
+			 # Ok::<(), Box<dyn std::error::Error>>(())\n```\n"
+		);
+	}
+
+	/// Merging leaves out blocks that rustdoc doesn't run as Rust, which keep their own trailer if they have one
+	#[test]
+	fn add_trailers_merges_only_doctests() {
+		let mut opts = options("merge_blocks = true");
+		opts.trailers.insert("python".to_owned(), "# done".to_owned());
+		assert_eq!(
+			trailers(
+				"```rust\nlet number: u8 = \"42\".parse()?;\n```\n\n```python\nprint(number)\n```\n\n\
+				 ```ignore\nnot_run();\n```\n\n```rust\nassert_eq!(number, 42);\n```\n",
+				&opts
+			),
+			"```rust,ignore\nlet number: u8 = \"42\".parse()?;\n```\n\n```python\nprint(number)\n\n# done\n```\n\n\
+			 ```ignore\nnot_run();\n```\n\n```rust\n# let number: u8 = \"42\".parse()?;\nassert_eq!(number, 42);\n\n\
+			 # Ok::<(), Box<dyn std::error::Error>>(())\n```\n"
+		);
+	}
+
+	/// The `merge_adjacent_blocks` option only combines blocks with nothing but blank lines between them
+	#[test]
+	fn add_trailers_merges_adjacent_blocks() {
+		assert_eq!(
+			trailers(
+				include_str!("../tests/fixtures/adjacent.md"),
+				&options("merge_adjacent_blocks = true")
+			),
+			"```rust,ignore\nlet text = \"42\";\n```\n\n```rust\n# let text = \"42\";\n\
+			 let number: u8 = text.parse()?;\n\
+			 \n# Ok::<(), Box<dyn std::error::Error>>(())\n```\n\nSeparately:\n\n```rust\n\
+			 let other: u8 = \"1\".parse()?;\n\
+			 \n# Ok::<(), Box<dyn std::error::Error>>(())\n```\n"
+		);
+	}
+
+	/// The `prelude` option adds hidden imports, allowing them to go unused unless told otherwise
+	#[test]
+	fn add_trailers_adds_prelude() {
+		assert_eq!(
+			trailers(
+				include_str!("../tests/fixtures/prelude.md"),
+				&options(r#"prelude = "use std::collections::HashMap;""#)
+			),
+			"```rust\n# #[allow(unused_imports)]\n# use std::collections::HashMap;\n\
+			 let map = HashMap::<u8, u8>::new();\n```\n\n\
+			 ```rust,ignore\nnot_run()?;\n```\n"
+		);
+		assert!(trailers(
+			include_str!("../tests/fixtures/prelude.md"),
+			&options(r#"prelude = "use std::collections::HashMap;", allow_unused_prelude = false"#)
+		)
+		.starts_with("```rust\n# use std::collections::HashMap;\nlet map"));
+	}
+
+	/// The `import_items` option imports the known items that blocks use without importing them
+	#[test]
+	fn add_trailers_imports_items() {
+		assert_eq!(
+			trailers(
+				include_str!("../tests/fixtures/imports.md"),
+				&options(r#"known_items = ["StuffDoer", "Other"], import_items = "some_crate""#)
+			),
+			"Use [`StuffDoer`] or [Other].\n\n```rust\n# use some_crate::StuffDoer;\nStuffDoer::do_stuff()?;\
+			 \n\n# Ok::<(), Box<dyn std::error::Error>>(())\n```\n\n```rust\nuse some_crate::Other;\nOther::new();\n\
+			 ```\n"
+		);
+	}
+
+	/// Rust blocks outside of lists and blockquotes are moved into an examples section under their headings
+	#[test]
+	fn examples_as_sections_moves_blocks() {
+		assert_eq!(
+			examples_as_sections(&trailers(
+				include_str!("../tests/fixtures/sections.md"),
+				&Options::default()
+			)),
+			"Some crate.\n\n## Parsing\n\nParsing things is easy:\n\n## Printing\n\nAdd it to `Cargo.toml`:\n\n\
+			 ```toml\n\
+			 [dependencies]\nsome_crate = \"1\"\n```\n\n# Examples\n\n## Parsing\n\n```rust\n\
+			 let value: i32 = \"1\".parse()?;\n\
+			 \n# Ok::<(), Box<dyn std::error::Error>>(())\n```\n\n## Printing\n\n```rust\nprintln!(\"hello\");\n```\n"
+		);
+	}
+
+	/// Only Rust blocks without an `ignore` attribute count as doctests
+	#[test]
+	fn count_doctests_leaves_out_ignored_blocks() {
+		assert_eq!(count_doctests(include_str!("../tests/fixtures/skipped.md")), 4);
+	}
+
+	/// The `target = "github"` mode removes hidden lines, and `docify(hidden)` blocks entirely
+	#[test]
+	fn strip_hidden_lines_removes_hidden_code() {
+		assert_eq!(
+			strip_hidden_lines(
+				"```rust\n# use some_crate::Thing;\nlet thing = Thing::new()?;\n```\n",
+				&Options::default()
+			),
+			"```rust\nlet thing = Thing::new()?;\n```\n"
+		);
+		assert_eq!(
+			strip_hidden_lines(include_str!("../tests/fixtures/hidden.md"), &Options::default()),
+			""
+		);
+	}
+
+	/// Blocks without a language, or with only rustdoc's attributes, count as doctests like rustdoc runs them
+	#[test]
+	fn note_languages_counts_unlabeled_blocks() {
+		let mut diagnostics = Diagnostics::new(true);
+		note_languages(
+			"```\nfirst();\n```\n\n```no_run\nsecond();\n```\n\n```ignore,rust\nskipped();\n```\n\n```text\nthird\n\
+			 ```\n",
+			&mut diagnostics,
+		);
+		assert!(matches!(
+			diagnostics.messages(),
+			[(_, message)] if message == "code block languages: (none) (1 block, 1 run as doctests), \
+				`ignore` (1 block, not run as doctests), `no_run` (1 block, 1 run as doctests), \
+				`text` (1 block, not run as doctests)"
+		));
+	}
+
+	/// Verbose diagnostics say why each Rust block was left without a trailer, and other languages are never skipped
+	/// for reasons that are about how rustdoc runs Rust code
+	#[test]
+	fn add_trailers_notes_skip_reasons() {
+		let mut diagnostics = Diagnostics::new(true);
+		add_trailers(
+			"```rust,ignore\nlet value = \"1\".parse::<i32>()?;\n```\n\n\
+			```rust\nfn main() {\n    run();\n}\n```\n\n\
+			```python\ndef main():\n    pass\n```\n\n\
+			```rust\nlet value = \"1\".parse::<i32>()?;\n```\n",
+			&Options::default(),
+			&mut diagnostics,
+			&mut Report::default(),
+		);
+		let notes: Vec<_> = diagnostics
+			.messages()
+			.iter()
+			.map(|(_, message)| message.as_str())
+			.filter(|message| message.contains("skipped"))
+			.collect();
+		assert_eq!(
+			notes,
+			[
+				"code block 1 (`rust,ignore`) skipped: has the `ignore` attribute",
+				"code block 2 (`rust`) skipped: defines its own `fn main`",
+			]
+		);
+	}
+
+	/// Each doctest becomes a standalone program the way rustdoc would build it: hidden lines are revealed, blocks with
+	/// their own `main` are kept as they are, blocks ending with `(())` return it from a wrapped function, and other
+	/// blocks are wrapped in a plain `main`
+	#[test]
+	fn scraped_examples_wrap_like_rustdoc() {
+		let readme = "```rust\nfn main() {\n    run();\n}\n```\n\n\
+			```rust\nlet value: i32 = \"1\".parse()?;\n# Ok::<(), std::num::ParseIntError>(())\n```\n\n\
+			```\n# let hidden = 1;\n##[derive(Debug)]\nstruct Thing;\n```\n\n\
+			```rust,ignore\nnot_scraped();\n```\n\n```text\nnot code\n```\n";
+		assert_eq!(
+			scraped_examples(readme),
+			[
+				"fn main() {\n    run();\n}\n",
+				"fn main() { fn _inner() -> Result<(), impl core::fmt::Debug> {\nlet value: i32 = \"1\".parse()?;\n\
+				 Ok::<(), std::num::ParseIntError>(())\n} _inner().unwrap() }\n",
+				"fn main() {\nlet hidden = 1;\n#[derive(Debug)]\nstruct Thing;\n}\n",
+			]
+		);
+	}
+
+	/// Trailers for other languages are appended regardless of the Rust-specific skipping rules
+	#[test]
+	fn add_trailers_to_other_languages() {
+		let opts = Options {
+			trailers: BTreeMap::from([("python".to_owned(), "# done".to_owned())]),
+			..Options::default()
+		};
+		assert_eq!(
+			trailers("```python\nprint(\"hi\")\n```\n", &opts),
+			"```python\nprint(\"hi\")\n\n# done\n```\n"
+		);
+	}
+
+	/// Trailers given for `rust` also apply to `rs` blocks, and vice versa
+	#[test]
+	fn add_trailers_treats_rs_as_rust() {
+		for lang in ["rust", "rs", "RS"] {
+			let input = format!(
+				r##""README.md", "https://docs.rs/some_crate/", "./", trailers = [("{lang}", "# Ok::<(), &str>(())")]"##
+			);
+			let opts = syn::parse_str::<Input>(&input).expect("options should parse").options;
+			assert_eq!(
+				trailers("```rs\nx()?;\n```\n\n```rust\ny()?;\n```\n", &opts),
+				"```rs\nx()?;\n\n# Ok::<(), &str>(())\n```\n\n```rust\ny()?;\n\n# Ok::<(), &str>(())\n```\n"
+			);
+		}
+	}
+}
@@ -0,0 +1,96 @@
+//! Non-fatal messages about the transformation of a readme
+
+use std::env;
+
+use syn::LitStr;
+
+/// Environment variable that enables verbose notes when set to anything other than an empty string or `0`
+pub(crate) const VERBOSE_VAR: &str = "DOCIFY_VERBOSE";
+
+/// Severity of a diagnostic message
+#[cfg_attr(test, derive(Debug, PartialEq, Eq))]
+pub(crate) enum Level {
+	/// Informational message, only emitted in verbose mode
+	Note,
+
+	/// Something is likely wrong with the readme or the options
+	Warning,
+}
+
+/// Collection of non-fatal messages produced while transforming a readme
+pub(crate) struct Diagnostics {
+	/// Whether notes are recorded
+	verbose: bool,
+
+	/// Messages recorded so far, in order
+	messages: Vec<(Level, String)>,
+}
+
+impl Diagnostics {
+	/// Creates an empty collection, recording notes only if verbose mode is enabled via [`VERBOSE_VAR`]
+	pub(crate) fn from_env() -> Self {
+		Self::new(env::var_os(VERBOSE_VAR).is_some_and(|value| !value.is_empty() && value != "0"))
+	}
+
+	/// Creates an empty collection, recording notes only if `verbose` is set
+	pub(crate) const fn new(verbose: bool) -> Self {
+		Self {
+			verbose,
+			messages: Vec::new(),
+		}
+	}
+
+	/// Gets the messages recorded so far, in order
+	#[cfg(test)]
+	pub(crate) fn messages(&self) -> &[(Level, String)] {
+		&self.messages
+	}
+
+	/// Records a note if verbose mode is enabled
+	pub(crate) fn note(&mut self, message: impl Into<String>) {
+		if self.verbose {
+			self.messages.push((Level::Note, message.into()));
+		}
+	}
+
+	/// Records a warning, unless the same one has already been recorded (such as by a step that runs once for each docs
+	/// URL)
+	pub(crate) fn warn(&mut self, message: impl Into<String>) {
+		let message = message.into();
+		if !self
+			.messages
+			.iter()
+			.any(|(level, recorded)| matches!(level, Level::Warning) && *recorded == message)
+		{
+			self.messages.push((Level::Warning, message));
+		}
+	}
+
+	/// Emits all recorded messages, prefixed with the readme they're about.
+	/// In strict mode, warnings are turned into errors spanned to the readme path instead.
+	#[allow(clippy::print_stderr)]
+	pub(crate) fn emit(self, readme: &LitStr, strict: bool) -> syn::Result<()> {
+		let mut errors: Option<syn::Error> = None;
+
+		for (level, message) in self.messages {
+			let level = match level {
+				Level::Warning if strict => {
+					let err = syn::Error::new_spanned(readme, format!("docify: {message}"));
+					match &mut errors {
+						Some(errors) => errors.combine(err),
+						None => errors = Some(err),
+					}
+					continue;
+				}
+				Level::Warning => "warning",
+				Level::Note => "note",
+			};
+
+			// Stable proc macros have no API for emitting warnings or notes, but cargo relays anything written to
+			// stderr during expansion, so that's the best available channel
+			eprintln!("{level}: docify ({}): {message}", readme.value());
+		}
+
+		errors.map_or(Ok(()), Err)
+	}
+}
@@ -0,0 +1,776 @@
+//! Transformations that apply to the readme document as a whole
+
+use std::{collections::BTreeMap, env, ffi::OsString, fmt::Write, ops::Range};
+
+use regex::{Captures, Regex, RegexBuilder};
+
+use crate::{diagnostics::Diagnostics, manifest, markdown, options::Footnotes};
+
+/// Environment variable that can hold the commit hash to include in the version footer
+pub(crate) const COMMIT_VAR: &str = "DOCIFY_COMMIT";
+
+/// Placeholder that's replaced with a list of the enabled crate features
+const FEATURES_PLACEHOLDER: &str = "{{features}}";
+
+/// Converts lone carriage return line breaks (as in classic Mac OS files) to line feeds, leaving `\r\n` and `\n` ones
+/// as they are. Rustdoc only splits code block contents on line feeds, so it would otherwise see each block as one
+/// line. Lines starting within any of the given verbatim ranges are left as-is.
+pub(crate) fn normalize_line_breaks(readme: &str, verbatim: &[Range<usize>]) -> String {
+	markdown::lines(readme)
+		.flat_map(|line| {
+			let newline = if line.newline == "\r" && !in_ranges(verbatim, line.start) {
+				"\n"
+			} else {
+				line.newline
+			};
+			[line.content, newline]
+		})
+		.collect()
+}
+
+/// Removes the longest common leading whitespace from all non-blank lines of the readme.
+/// Every line (prose and code block contents alike) loses the same prefix, so relative indentation is preserved.
+/// Lines starting within any of the given verbatim ranges don't count towards the common prefix, and only lose it if
+/// they start with it, keeping everything else (including trailing and blank-line whitespace) as-is.
+pub(crate) fn dedent(readme: &str, verbatim: &[Range<usize>]) -> String {
+	let indent = markdown::lines(readme)
+		.filter(|line| !line.content.trim().is_empty() && !in_ranges(verbatim, line.start))
+		.map(|line| {
+			&line.content[..line
+				.content
+				.find(|c| c != ' ' && c != '\t')
+				.unwrap_or(line.content.len())]
+		})
+		.reduce(common_prefix)
+		.unwrap_or("");
+
+	if indent.is_empty() {
+		return readme.to_owned();
+	}
+
+	markdown::lines(readme)
+		.flat_map(|line| {
+			let content = match line.content.strip_prefix(indent) {
+				Some(rest) if in_ranges(verbatim, line.start) => rest,
+				_ if in_ranges(verbatim, line.start) => line.content,
+				Some(rest) => rest,
+				None if line.content.trim().is_empty() => "",
+				None => line.content,
+			};
+			[content, line.newline]
+		})
+		.collect()
+}
+
+/// Checks whether a position is within any of the given ranges
+fn in_ranges(ranges: &[Range<usize>], pos: usize) -> bool {
+	ranges.iter().any(|range| range.contains(&pos))
+}
+
+/// Gets the longest common prefix of two strings
+fn common_prefix<'a>(a: &'a str, b: &str) -> &'a str {
+	let len = a
+		.char_indices()
+		.zip(b.chars())
+		.find(|((_, a), b)| a != b)
+		.map_or_else(|| a.len().min(b.len()), |((idx, _), _)| idx);
+	&a[..len]
+}
+
+/// Removes all raw HTML `<table>` elements (including nested ones) outside of code from the readme, along with the rest
+/// of the line following each one's closing tag if it's only whitespace.
+/// A table without a closing tag is kept, along with the rest of the readme, with a warning.
+pub(crate) fn strip_html_tables(readme: &str, diagnostics: &mut Diagnostics) -> String {
+	let masked = markdown::mask_code(readme);
+	let re = RegexBuilder::new(r"<table\b[^>]*>|</table\s*>[ \t]*(?:\r\n|\r|\n)?")
+		.case_insensitive(true)
+		.build()
+		.expect("unable to build table regex");
+
+	let mut output = String::with_capacity(readme.len());
+	let mut depth = 0_usize;
+	let mut last = 0;
+	for tag in re.find_iter(&masked) {
+		if tag.as_str().starts_with("</") {
+			// Ignore stray closing tags outside of any table
+			let Some(new_depth) = depth.checked_sub(1) else {
+				continue;
+			};
+			depth = new_depth;
+			if depth == 0 {
+				last = tag.end();
+			}
+		} else {
+			if depth == 0 {
+				output.push_str(&readme[last..tag.start()]);
+				last = tag.start();
+			}
+			depth = depth.saturating_add(1);
+		}
+	}
+
+	if depth > 0 {
+		diagnostics.warn("`<table>` element has no closing tag, so it and the rest of the readme are kept");
+	}
+	output.push_str(&readme[last..]);
+	output
+}
+
+/// Converts GFM footnotes (`[^label]` references along with their `[^label]: text` definitions) outside of code, either
+/// inlining each definition's text as a parenthetical at its references or dropping them entirely.
+/// References without a definition are left as-is.
+pub(crate) fn convert_footnotes(readme: &str, footnotes: Footnotes) -> String {
+	if footnotes == Footnotes::Keep {
+		return readme.to_owned();
+	}
+
+	let masked = markdown::mask_code(readme);
+	let def_re = Regex::new(r"^ {0,3}\[\^([^\]\s]+)\]:[ \t]*(.*)$").expect("unable to build footnote definition regex");
+	let ref_re = Regex::new(r"\[\^([^\]\s]+)\]").expect("unable to build footnote reference regex");
+
+	// Find all definitions (including their indented continuation lines), which are removed either way
+	let mut definitions = BTreeMap::new();
+	let mut edits: Vec<(Range<usize>, String)> = Vec::new();
+	let mut current: Option<String> = None;
+	for line in markdown::lines(&masked) {
+		let original = &readme[line.start..line.end()];
+		if let Some(caps) = def_re.captures(line.content) {
+			let (Some(label), Some(text)) = (caps.get(1), caps.get(2)) else {
+				continue;
+			};
+			let label = original[label.range()].to_lowercase();
+			definitions.insert(label.clone(), original[text.range()].trim().to_owned());
+			edits.push((line.start..line.next_start(), String::new()));
+			current = Some(label);
+		} else if let Some(text) = current
+			.as_ref()
+			.filter(|_| line.content.starts_with([' ', '\t']) && !line.content.trim().is_empty())
+			.and_then(|label| definitions.get_mut(label))
+		{
+			text.push(' ');
+			text.push_str(original.trim());
+			edits.push((line.start..line.next_start(), String::new()));
+		} else {
+			current = None;
+		}
+	}
+
+	// Replace the references to defined footnotes
+	for caps in ref_re.captures_iter(&masked) {
+		let (Some(whole), Some(label)) = (caps.get(0), caps.get(1)) else {
+			continue;
+		};
+		let in_definition = edits.iter().any(|(range, _)| range.contains(&whole.start()));
+		let Some(text) = definitions
+			.get(&readme[label.range()].to_lowercase())
+			.filter(|_| !in_definition)
+		else {
+			continue;
+		};
+
+		let replacement = match footnotes {
+			Footnotes::Inline if readme[..whole.start()].ends_with(char::is_whitespace) => format!("({text})"),
+			Footnotes::Inline => format!(" ({text})"),
+			Footnotes::Keep | Footnotes::Drop => String::new(),
+		};
+		edits.push((whole.range(), replacement));
+	}
+	edits.sort_by_key(|(range, _)| range.start);
+
+	let mut output = String::with_capacity(readme.len());
+	let mut end = 0;
+	for (range, replacement) in &edits {
+		output.push_str(&readme[end..range.start]);
+		output.push_str(replacement);
+		end = range.end;
+	}
+	output.push_str(&readme[end..]);
+
+	// Definitions are usually at the end of the readme, so don't leave the blank lines that preceded them behind
+	if end == readme.len() && !edits.is_empty() {
+		let newline = markdown::lines(readme).last().map_or("", |line| line.newline);
+		output.truncate(output.trim_end().len());
+		output.push_str(newline);
+	}
+	output
+}
+
+/// Replaces whole-word occurrences of a crate name in the prose of the readme (outside of code and
+/// [link targets](link_targets), since URLs keep using the old name) with a new name.
+/// If `in_code` is set, paths starting with the crate name (`old_name::...` or `extern crate old_name`) in Rust code
+/// blocks are also rewritten, using the names with hyphens turned into underscores.
+pub(crate) fn rename_crate(readme: &str, old: &str, new: &str, in_code: bool) -> String {
+	let masked = markdown::mask_code(readme);
+	let targets = link_targets(&masked);
+	let is_word_char = |c: char| c.is_alphanumeric() || c == '_' || c == '-';
+
+	let mut edits: Vec<(Range<usize>, String)> = masked
+		.match_indices(old)
+		.filter(|&(idx, _)| {
+			if in_ranges(&targets, idx) {
+				return false;
+			}
+			let before = masked[..idx].chars().next_back();
+			let after = masked[idx.saturating_add(old.len())..].chars().next();
+			!before.is_some_and(is_word_char) && !after.is_some_and(is_word_char)
+		})
+		.map(|(idx, _)| (idx..idx.saturating_add(old.len()), new.to_owned()))
+		.collect();
+
+	if in_code {
+		let (old, new) = (old.replace('-', "_"), new.replace('-', "_"));
+		let re = Regex::new(&format!(
+			r"(?P<before>^|[^\w:])(?P<name>{0})(?P<after>\s*::)|(?P<extern>\bextern\s+crate\s+){0}\b",
+			regex::escape(&old)
+		))
+		.expect("unable to build crate path regex");
+
+		let rust_blocks = markdown::fenced_blocks(readme).into_iter().filter(|block| {
+			block.close.is_some()
+				&& block
+					.info_tokens()
+					.next()
+					.is_some_and(|lang| lang.eq_ignore_ascii_case("rust") || lang.eq_ignore_ascii_case("rs"))
+		});
+		for block in rust_blocks {
+			let body = re.replace_all(block.body, |caps: &Captures<'_>| match caps.name("extern") {
+				Some(prefix) => format!("{}{new}", prefix.as_str()),
+				None => format!("{}{new}{}", &caps["before"], &caps["after"]),
+			});
+			edits.push((block.body_range, body.into_owned()));
+		}
+	}
+	edits.sort_by_key(|(range, _)| range.start);
+
+	let mut output = String::with_capacity(readme.len());
+	let mut end = 0;
+	for (range, replacement) in &edits {
+		output.push_str(&readme[end..range.start]);
+		output.push_str(replacement);
+		end = range.end;
+	}
+	output.push_str(&readme[end..]);
+	output
+}
+
+/// Finds the byte ranges of link targets in the (masked) readme: inline link and reference definition destinations,
+/// autolinks, and bare URLs
+fn link_targets(masked: &str) -> Vec<Range<usize>> {
+	let re = Regex::new(concat!(
+		r"\]\((?P<inline>[^\s)]+)",
+		r"|(?m:^ {0,3}\[[^\]]+\]:[ \t]*(?:\r\n|\r|\n)?[ \t]*(?P<def>\S+))",
+		r"|<(?P<auto>[A-Za-z][A-Za-z0-9+.-]*:[^\s<>]*)>",
+		r"|(?P<bare>\bhttps?://[^\s<>]+)",
+	))
+	.expect("unable to build link target regex");
+
+	re.captures_iter(masked)
+		.filter_map(|caps| {
+			["inline", "def", "auto", "bare"]
+				.into_iter()
+				.find_map(|name| caps.name(name))
+		})
+		.map(|target| target.range())
+		.collect()
+}
+
+/// Lowercases the language token of every fenced code block's info string if `lowercase` is set, and replaces `rs` (in
+/// any case) with `rust` if `canonical` is set
+pub(crate) fn normalize_langs(readme: &str, lowercase: bool, canonical: bool) -> String {
+	let mut output = String::with_capacity(readme.len());
+	let mut end = 0;
+	for block in markdown::fenced_blocks(readme) {
+		let Some(lang) = block.info_tokens().next() else {
+			continue;
+		};
+
+		let normalized = if canonical && lang.eq_ignore_ascii_case("rs") {
+			"rust".to_owned()
+		} else if lowercase {
+			lang.to_lowercase()
+		} else {
+			continue;
+		};
+
+		// Only separators can precede the language token, so its first occurrence is the token itself
+		let skipped = block.info.find(lang).unwrap_or_default();
+		let start = block
+			.range
+			.start
+			.saturating_add(block.fence.len())
+			.saturating_add(skipped);
+		output.push_str(&readme[end..start]);
+		output.push_str(&normalized);
+		end = start.saturating_add(lang.len());
+	}
+	output.push_str(&readme[end..]);
+	output
+}
+
+/// Gets the names of the crate features enabled for the build, as indicated by the `CARGO_FEATURE_<NAME>` environment
+/// variables, sorted. Cargo uppercases feature names and turns hyphens into underscores for those, so each name is
+/// matched up with a feature in the manifest's `[features]` table to get its original spelling, falling back to the
+/// name lowercased if there's no such feature.
+pub(crate) fn enabled_features() -> Vec<String> {
+	let declared = manifest::read(|name| env::var_os(name))
+		.map_or_else(Vec::new, |manifest| manifest::table_keys(&manifest, "features"));
+	feature_names(
+		env::vars_os().filter_map(|(name, _)| name.into_string().ok()),
+		&declared,
+	)
+}
+
+/// Gets the sorted names of the features indicated by the `CARGO_FEATURE_<NAME>` variables among the given environment
+/// variable names, spelled like the matching declared features (see [`enabled_features`])
+fn feature_names(vars: impl IntoIterator<Item = String>, declared: &[String]) -> Vec<String> {
+	let mut features: Vec<String> = vars
+		.into_iter()
+		.filter_map(|var| {
+			let name = var.strip_prefix("CARGO_FEATURE_")?;
+			Some(
+				declared
+					.iter()
+					.find(|feature| feature.to_uppercase().replace('-', "_") == name)
+					.cloned()
+					.unwrap_or_else(|| name.to_lowercase()),
+			)
+		})
+		.collect();
+	features.sort();
+	features
+}
+
+/// Replaces every `{{features}}` placeholder outside of code with a bullet list of the given crate features (see
+/// [`enabled_features`]). Lines of the list after the first get the same indentation (and blockquote markers) as the
+/// placeholder, and use the readme's line breaks.
+pub(crate) fn expand_features(readme: &str, features: impl IntoIterator<Item = String>) -> String {
+	let masked = markdown::mask_code(readme);
+	if !masked.contains(FEATURES_PLACEHOLDER) {
+		return readme.to_owned();
+	}
+
+	let items = features
+		.into_iter()
+		.map(|feature| format!("- `{feature}`"))
+		.collect::<Vec<_>>();
+	let newline = markdown::lines(readme).next().map_or("\n", |line| line.newline);
+	let newline = if newline.is_empty() { "\n" } else { newline };
+
+	let mut output = String::with_capacity(readme.len());
+	let mut end = 0;
+	for (idx, _) in masked.match_indices(FEATURES_PLACEHOLDER) {
+		let line_start = readme[..idx].rfind(['\n', '\r']).map_or(0, |pos| pos.saturating_add(1));
+		let indent = Some(&readme[line_start..idx])
+			.filter(|prefix| prefix.trim_start_matches([' ', '\t', '>']).is_empty())
+			.unwrap_or_default();
+
+		output.push_str(&readme[end..idx]);
+		if items.is_empty() {
+			output.push_str("*No features are enabled.*");
+		} else {
+			output.push_str(&items.join(&format!("{newline}{indent}")));
+		}
+		end = idx.saturating_add(FEATURES_PLACEHOLDER.len());
+	}
+	output.push_str(&readme[end..]);
+	output
+}
+
+/// Extracts the regions of the readme between `<!-- docify:start -->` and `<!-- docify:end -->` markers (outside of
+/// code), if there are any, concatenating them in document order with a blank line between each.
+/// A start marker without a matching end marker includes the rest of the readme, with a warning. End markers without
+/// a preceding start marker are ignored.
+pub(crate) fn extract_regions(readme: &str, diagnostics: &mut Diagnostics) -> String {
+	let masked = markdown::mask_code(readme);
+	let marker_re = RegexBuilder::new(r"<!--\s*docify:(start|end)\s*-->(?:\r\n|\r|\n)?")
+		.case_insensitive(true)
+		.build()
+		.expect("unable to build region marker regex");
+
+	let mut regions = Vec::new();
+	let mut region_start = None;
+	for marker in marker_re.captures_iter(&masked) {
+		let Some(whole) = marker.get(0) else {
+			continue;
+		};
+
+		match (region_start, marker[1].eq_ignore_ascii_case("start")) {
+			(None, true) => region_start = Some(whole.end()),
+			(Some(start), false) => {
+				regions.push(&readme[start..whole.start()]);
+				region_start = None;
+			}
+			_ => {}
+		}
+	}
+
+	if let Some(start) = region_start {
+		diagnostics
+			.warn("`docify:start` marker has no matching `docify:end` marker, so the rest of the readme is included");
+		regions.push(&readme[start..]);
+	}
+
+	if regions.is_empty() {
+		readme.to_owned()
+	} else {
+		regions.join("\n")
+	}
+}
+
+/// Removes the readme's title (its first heading, if it's a level 1 heading with nothing but blank lines before it),
+/// along with the blank lines following it
+pub(crate) fn strip_title(readme: &str) -> String {
+	let Some(title) = markdown::headings(readme).into_iter().next() else {
+		return readme.to_owned();
+	};
+	if title.level != 1 || !readme[..title.range.start].trim().is_empty() {
+		return readme.to_owned();
+	}
+
+	readme[title.range.end..].trim_start_matches(['\r', '\n']).to_owned()
+}
+
+/// Extracts the contents of the first section of the readme whose heading text matches the given text
+/// (case-insensitively), up to the next heading of the same or a higher level, without the heading itself.
+/// Returns `None` if there's no such section.
+pub(crate) fn extract_section(readme: &str, heading: &str) -> Option<String> {
+	let headings = markdown::headings(readme);
+	let (idx, section) = headings
+		.iter()
+		.enumerate()
+		.find(|(_, candidate)| candidate.text.eq_ignore_ascii_case(heading.trim()))?;
+	let end = headings[idx.saturating_add(1)..]
+		.iter()
+		.find(|next| next.level <= section.level)
+		.map_or(readme.len(), |next| next.range.start);
+
+	let contents = readme[section.range.end..end].trim_start_matches(['\r', '\n']);
+	let newline = markdown::lines(contents).next().map_or("\n", |line| line.newline);
+	Some(format!("{}{newline}", contents.trim_end()))
+}
+
+/// Makes the final output safe to embed as a `#[doc]` value and to write to files that other tools read. NUL characters
+/// are replaced with U+FFFD (as the markdown spec requires anyway), and Unicode text direction controls (which can make
+/// text display in a different order than it's stored) are replaced with HTML character references in prose, where
+/// rustdoc renders them the same. They can't be replaced in code without changing its meaning, so a warning is recorded
+/// for each one found there instead, since rustc rejects them in doctests by default.
+pub(crate) fn sanitize(readme: &str, diagnostics: &mut Diagnostics) -> String {
+	let masked = markdown::mask_code(readme);
+	let is_direction_control = |c: char| matches!(c, '\u{202a}'..='\u{202e}' | '\u{2066}'..='\u{2069}');
+
+	let mut output = String::with_capacity(readme.len());
+	for (idx, c) in readme.char_indices() {
+		match c {
+			'\0' => output.push(char::REPLACEMENT_CHARACTER),
+			_ if is_direction_control(c) && masked[idx..].starts_with(c) => {
+				let _ = write!(output, "&#x{:X};", u32::from(c));
+			}
+			_ if is_direction_control(c) => {
+				diagnostics.warn(format!(
+					"line {} contains a Unicode text direction control character (U+{:04X}) in code",
+					readme[..idx].matches('\n').count().saturating_add(1),
+					u32::from(c),
+				));
+				output.push(c);
+			}
+			_ => output.push(c),
+		}
+	}
+	output
+}
+
+/// Appends a footer noting the crate version the docs were generated for (from `CARGO_PKG_VERSION`), along with the
+/// commit they were generated from if [`COMMIT_VAR`] is set, looking the variables up with `var`
+pub(crate) fn append_version_footer(readme: &str, var: impl Fn(&str) -> Option<OsString>) -> String {
+	let var = |name| var(name).and_then(|value| value.into_string().ok());
+	let version = var("CARGO_PKG_VERSION").unwrap_or_default();
+	let commit = var(COMMIT_VAR).filter(|commit| !commit.trim().is_empty());
+	let newline = markdown::lines(readme).next().map_or("\n", |line| line.newline);
+	let newline = if newline.is_empty() { "\n" } else { newline };
+
+	let mut output = readme.trim_end().to_owned();
+	output.extend([
+		newline,
+		newline,
+		"---",
+		newline,
+		newline,
+		"*Docs generated for v",
+		&version,
+	]);
+	if let Some(commit) = commit {
+		output.extend([" (commit `", commit.trim(), "`)"]);
+	}
+	output.extend(["*", newline]);
+	output
+}
+
+#[cfg(test)]
+mod tests {
+	//! Tests for the whole-document transformations
+
+	use super::*;
+	use crate::{codeblocks, diagnostics::Level, test_util::vars};
+
+	/// The common indentation is removed from every line, whatever its line break
+	#[test]
+	fn dedent_handles_all_line_breaks() {
+		assert_eq!(
+			dedent("  # Title\r\n\r\n  ```rust\r    nested();\r  ```\n  \n  Prose.", &[]),
+			"# Title\r\n\r\n```rust\r  nested();\r```\n\nProse."
+		);
+	}
+
+	/// Dedenting only removes the common prefix from `docify(verbatim)` blocks, and converting lone carriage returns
+	/// leaves them alone
+	#[test]
+	fn dedent_keeps_verbatim_blocks() {
+		let readme = include_str!("../tests/fixtures/verbatim.md");
+		let readme = dedent(readme, &codeblocks::verbatim_ranges(readme));
+		assert_eq!(
+			normalize_line_breaks(&readme, &codeblocks::verbatim_ranges(&readme)),
+			"Intro.\n\n```rust,docify(verbatim)\nlet value: i32 = \"1\".parse()?;  \r\t let other = value;\n```\n\
+			 \n\
+			 ```rust\nlet value = 2;\n```\n"
+		);
+	}
+
+	/// A `docify(verbatim)` block in an indented readme stays a fenced block, keeping its relative whitespace
+	#[test]
+	fn dedent_indented_verbatim_block() {
+		let readme =
+			"    Intro.\n\n    ```rust,docify(verbatim)\n    fn main() {\n    \tlet x = 1;  \n    }\n    ```\n";
+		assert_eq!(
+			dedent(readme, &codeblocks::verbatim_ranges(readme)),
+			"Intro.\n\n```rust,docify(verbatim)\nfn main() {\n\tlet x = 1;  \n}\n```\n"
+		);
+	}
+
+	/// Dedenting first lets an indented title be recognized
+	#[test]
+	fn dedent_before_strip_title() {
+		let readme = dedent(include_str!("../tests/fixtures/indented.md"), &[]);
+		assert_eq!(
+			readme,
+			"# Title\n\nSome prose.\n\n```rust\nfn main() {\n    println!(\"hi\");\n}\n```\n"
+		);
+		assert_eq!(
+			strip_title(&readme),
+			"Some prose.\n\n```rust\nfn main() {\n    println!(\"hi\");\n}\n```\n"
+		);
+	}
+
+	/// Lone carriage returns become line feeds
+	#[test]
+	fn normalize_line_breaks_converts_carriage_returns() {
+		assert_eq!(
+			normalize_line_breaks(include_str!("../tests/fixtures/cr.md"), &[]),
+			"Old line breaks.\n\n```rust\nlet value: i32 = \"42\".parse()?;\n```\n"
+		);
+	}
+
+	/// Footnote references are inlined or dropped, leaving code alone, and their definitions are removed
+	#[test]
+	fn convert_footnotes_inlines_or_drops() {
+		let readme = include_str!("../tests/fixtures/footnotes.md");
+		assert_eq!(
+			convert_footnotes(readme, Footnotes::Inline),
+			"The readme (Usually `README.md`.) is read and rewritten (See the crate docs.).\n\
+			 Patterns like `[^1]` are left alone.\n"
+		);
+		assert_eq!(
+			convert_footnotes(readme, Footnotes::Drop),
+			"The readme is read and rewritten.\nPatterns like `[^1]` are left alone.\n"
+		);
+	}
+
+	/// Whole-word mentions of the old name are replaced in prose, and paths in code only if asked
+	#[test]
+	fn rename_crate_replaces_whole_words() {
+		let readme = include_str!("../tests/fixtures/renamed.md");
+		let renamed = rename_crate(readme, "old-crate", "new-crate", false);
+		assert!(renamed.starts_with("Add new-crate to your dependencies (but not old-crate-extras or my-old-crate)."));
+		assert!(renamed.contains("use old_crate::Thing;\n"));
+		assert!(rename_crate(readme, "old-crate", "new-crate", true).ends_with(
+			"```rust\nuse new_crate::Thing;\nlet thing = new_crate::Thing::new();\n\
+			 let my_old_crate = other::old_crate::value();\n```\n"
+		));
+	}
+
+	/// Link destinations, autolinks and bare URLs keep the old name, while link text and reference labels are renamed
+	#[test]
+	fn rename_crate_leaves_urls_alone() {
+		assert_eq!(
+			rename_crate(
+				include_str!("../tests/fixtures/renamed_links.md"),
+				"old-crate",
+				"new-crate",
+				false
+			),
+			"See [new-crate](https://crates.io/crates/old-crate), <https://docs.rs/old-crate> and \
+			 https://lib.rs/old-crate.\n\n[new-crate][repo] is on GitHub.\n\n[repo]: https://github.com/me/old-crate\n"
+		);
+	}
+
+	/// Languages are lowercased, and `rs` becomes `rust` only when asked
+	#[test]
+	fn normalize_langs_canonicalizes() {
+		let readme = include_str!("../tests/fixtures/langs.md");
+		assert_eq!(
+			normalize_langs(readme, true, false),
+			"```rust\nlet a = 1;\n```\n\n```rs,no_run\nlet b = 2;\n```\n\n```toml\na = 1\n```\n"
+		);
+		assert_eq!(
+			normalize_langs(readme, true, true),
+			"```rust\nlet a = 1;\n```\n\n```rust,no_run\nlet b = 2;\n```\n\n```toml\na = 1\n```\n"
+		);
+	}
+
+	/// Setext titles are removed, while a thematic break after them isn't mistaken for an underline
+	#[test]
+	fn strip_title_handles_setext() {
+		assert!(strip_title(include_str!("../tests/fixtures/setext.md"))
+			.starts_with("Intro paragraph.\n\n---\n\nUsage\n-----\n"));
+	}
+
+	/// A section runs up to the next heading of the same level, and leaves out its own heading
+	#[test]
+	fn extract_section_finds_setext_heading() {
+		let readme = include_str!("../tests/fixtures/setext.md");
+		assert_eq!(
+			extract_section(readme, "usage").as_deref(),
+			Some("Call the macro.\n\n### Details\n\nMore details.\n")
+		);
+		assert_eq!(extract_section(readme, "Missing"), None);
+	}
+
+	/// Marked regions are joined, and a lone start marker includes the rest of the readme with a warning
+	#[test]
+	fn extract_regions_joins_regions() {
+		let mut diagnostics = Diagnostics::new(false);
+		assert_eq!(
+			extract_regions(include_str!("../tests/fixtures/regions.md"), &mut diagnostics),
+			"First region.\n\nSecond region.\n\nThe rest, after a lone start marker.\n"
+		);
+		assert!(matches!(diagnostics.messages(), [(Level::Warning, _)]));
+	}
+
+	/// Direction controls become character references in prose and are warned about in code, and NULs are replaced
+	#[test]
+	fn sanitize_replaces_special_characters() {
+		let mut diagnostics = Diagnostics::new(false);
+		assert_eq!(
+			sanitize(include_str!("../tests/fixtures/direction.md"), &mut diagnostics),
+			"Some &#x202E;reversed&#x202C; text.\n"
+		);
+		assert_eq!(
+			sanitize("a\0b `\u{2066}`\n", &mut diagnostics),
+			"a\u{fffd}b `\u{2066}`\n"
+		);
+		assert!(matches!(
+			diagnostics.messages(),
+			[(Level::Warning, message)] if message.contains("(U+2066) in code")
+		));
+	}
+
+	/// The footer goes after a thematic break, with the commit only if there is one
+	#[test]
+	fn append_version_footer_adds_break() {
+		let readme = "See [the license](./LICENSE.md).\n";
+		assert_eq!(
+			append_version_footer(readme, vars(&[("CARGO_PKG_VERSION", "1.2.3")])),
+			"See [the license](./LICENSE.md).\n\n---\n\n*Docs generated for v1.2.3*\n"
+		);
+		assert_eq!(
+			append_version_footer(readme, vars(&[("CARGO_PKG_VERSION", "1.2.3"), (COMMIT_VAR, " ")])),
+			"See [the license](./LICENSE.md).\n\n---\n\n*Docs generated for v1.2.3*\n"
+		);
+		assert_eq!(
+			append_version_footer(
+				readme,
+				vars(&[("CARGO_PKG_VERSION", "1.2.3"), (COMMIT_VAR, "abc1234\n")])
+			),
+			"See [the license](./LICENSE.md).\n\n---\n\n*Docs generated for v1.2.3 (commit `abc1234`)*\n"
+		);
+	}
+
+	/// Only the language token of each info string is changed, wherever it starts
+	#[test]
+	fn normalize_langs_rewrites_only_the_language() {
+		assert_eq!(
+			normalize_langs(
+				"```{r}\nx\n```\n\n``` RS,ignore\ny\n```\n\n```Rust{1}\nz\n```\n",
+				true,
+				true
+			),
+			"```{r}\nx\n```\n\n``` rust,ignore\ny\n```\n\n```rust{1}\nz\n```\n"
+		);
+	}
+
+	/// Feature names are spelled like the declared features they match, or lowercased if there's no match
+	#[test]
+	fn feature_names_restore_hyphens() {
+		let vars = [
+			"CARGO_FEATURE_ASYNC_IO",
+			"CARGO_FEATURE_SERDE",
+			"CARGO_PKG_NAME",
+			"PATH",
+		]
+		.map(str::to_owned);
+		let declared = ["async-io", "default"].map(str::to_owned);
+		assert_eq!(feature_names(vars, &declared), ["async-io", "serde"]);
+	}
+
+	/// Each placeholder outside of code becomes a list of the features, indented like the placeholder, with the
+	/// readme's line breaks
+	#[test]
+	fn expand_features_lists_features() {
+		let features = || ["async-io", "serde"].map(str::to_owned);
+		assert_eq!(
+			expand_features("Features:\r\n\r\n{{features}}\r\n\r\n`{{features}}`\r\n", features()),
+			"Features:\r\n\r\n- `async-io`\r\n- `serde`\r\n\r\n`{{features}}`\r\n"
+		);
+		assert_eq!(
+			expand_features("- Features:\n\n  {{features}}\n", features()),
+			"- Features:\n\n  - `async-io`\n  - `serde`\n"
+		);
+		assert_eq!(
+			expand_features(include_str!("../tests/fixtures/features.md"), []),
+			"Enabled features:\n\n*No features are enabled.*\n\nNot in `{{features}}` code, though.\n"
+		);
+	}
+
+	/// Tables in inline code and code blocks are left alone
+	#[test]
+	fn strip_html_tables_ignores_code() {
+		let mut diagnostics = Diagnostics::new(false);
+		let readme = "Avoid `<table>` tags.\n\n## Usage\n\n```html\n<table></table>\n```\n\nMore text.\n";
+		assert_eq!(strip_html_tables(readme, &mut diagnostics), readme);
+		assert!(diagnostics.messages().is_empty());
+	}
+
+	/// Tables (including nested ones) are removed along with the rest of the line following their closing tag
+	#[test]
+	fn strip_html_tables_removes_tables() {
+		let mut diagnostics = Diagnostics::new(false);
+		let readme = "Before.\n\n<table><tr><td><table></table></td></tr></table>  \nAfter.\n";
+		assert_eq!(strip_html_tables(readme, &mut diagnostics), "Before.\n\nAfter.\n");
+		assert_eq!(
+			strip_html_tables(include_str!("../tests/fixtures/table.md"), &mut diagnostics),
+			"Before the table.\n\nAfter the table.\n"
+		);
+		assert!(diagnostics.messages().is_empty());
+	}
+
+	/// An unclosed table is kept along with the rest of the readme, with a warning
+	#[test]
+	fn strip_html_tables_keeps_unclosed_table() {
+		let mut diagnostics = Diagnostics::new(false);
+		let readme = "<table></table>\nBefore.\n\n<table>\n\n## Usage\n\nMore text.\n";
+		assert_eq!(
+			strip_html_tables(readme, &mut diagnostics),
+			"Before.\n\n<table>\n\n## Usage\n\nMore text.\n"
+		);
+		assert!(matches!(diagnostics.messages(), [(Level::Warning, message)] if message.contains("no closing tag")));
+	}
+}
@@ -111,7 +111,6 @@
 	clippy::same_name_method,
 	clippy::self_named_module_files,
 	clippy::str_to_string,
-	clippy::string_to_string,
 	clippy::suspicious_xor_used_as_pow,
 	clippy::tests_outside_test_module,
 	clippy::try_err,
@@ -124,14 +123,37 @@
 	clippy::verbose_file_reads
 )]
 
-use std::{env, fs, path::Path};
+mod codeblocks;
+mod diagnostics;
+mod document;
+mod links;
+mod manifest;
+mod markdown;
+mod options;
+mod report;
+#[cfg(test)]
+mod test_util;
+
+use std::{
+	env,
+	ffi::OsString,
+	fs,
+	path::{Path, PathBuf},
+};
 
 use quote::ToTokens;
-use regex::RegexBuilder;
-use syn::{parse::Parser, punctuated::Punctuated, spanned::Spanned};
+use syn::LitStr;
+
+use diagnostics::Diagnostics;
+use options::{Config, HtmlTables, Input, Invocation, Options, Target};
+use report::{Report, REPORT_VAR};
+
+/// Environment variable that, when set, holds the path of a file to also write the transformed readme to
+const EMIT_PATH_VAR: &str = "DOCIFY_EMIT_PATH";
 
-/// Type to parse the macro input into
-type Args = Punctuated<syn::LitStr, syn::Token![,]>;
+/// Environment variable that, when set, holds the path of a directory to write each of the readme's doctests to as a
+/// standalone example program
+const EXAMPLES_DIR_VAR: &str = "DOCIFY_EXAMPLES_DIR";
 
 /// Takes an input readme file path (relative to Cargo.toml), reads the contents of the file,
 /// adds `# Ok::<(), Box<dyn std::error::Error>>(())` to the end of all Rust code blocks inside it,
@@ -147,61 +169,959 @@ type Args = Punctuated<syn::LitStr, syn::Token![,]>;
 /// }
 /// ```
 ///
+/// # Code block trailers
+/// Rust code blocks receive the trailer unless any of the following apply, in which case they're left as-is:
+/// - The block contains a line consisting of just `// docify:skip` (which is removed from the output)
+/// - The block has an `ignore` attribute (e.g. ` ```rust,ignore `)
+/// - The block defines its own `fn main`
+/// - The block's last line is already an `Ok::<...>(...)` expression (e.g. `Ok::<(), MyError>(())`), which rustdoc uses
+///   as the return value
+/// - The block doesn't use the question mark operator, so it has no need for a trailer
+/// - The block's index is listed in the [`skip_blocks`](#skip_blocks) option
+/// - The block is inside a blockquote and the [`skip_blockquoted_code`](#skip_blockquoted_code) option is enabled
+///
+/// Set the `DOCIFY_VERBOSE` environment variable to get a note about each skipped block and the reason it was skipped.
+/// A note listing every distinct language of the readme's code blocks, along with how many blocks of each are run as
+/// doctests, is included as well, which helps with spotting misspelled languages and examples that aren't tested.
+///
+/// Blocks that end with an `Ok(...)` expression without a type annotation instead have their code run inside a hidden
+/// function returning `Result<(), Box<dyn std::error::Error>>`, since rustdoc can't infer the error type.
+/// Blocks that use the question mark operator on `Option`s rather than `Result`s can contain a line consisting of just
+/// `// docify:option` (which is removed from the output) to instead have their code run inside a hidden function
+/// returning `Option<()>`, which panics if it returns `None` (just like the `Result` trailer panics for `Err`).
+///
+/// A few attributes in a block's info string (which are removed from the output) change how it's handled:
+/// - `docify(hidden)` hides all of the block's lines from the rendered docs, while still running it as a doctest
+/// - `docify(verbatim)` keeps the block's whitespace when transformations of the whole readme affect it, like the
+///   conversion of lone carriage returns (see below), only losing the common prefix removed by [`dedent`](#dedent)
+/// - `docify(feature = "...")` wraps the block's code in a hidden block with a `#[cfg(feature = "...")]` attribute, so
+///   that it's only compiled with the feature enabled (unless it defines its own `fn main` or ends with `Ok(...)`)
+///
+/// For example, this block is only compiled with the `serde` feature:
+/// ````md
+/// ```rust,docify(feature = "serde")
+/// let value: Value = serde_json::from_str("1")?;
+/// ```
+/// ````
+///
+/// A block whose first line consists of just `// docify:edition` followed by an edition (e.g. `// docify:edition 2015`)
+/// is tested with that edition instead of the crate's: the line is removed and an `edition` attribute is added to its
+/// info string, which allows demonstrating syntax from different editions in the same readme.
+///
+/// Attributes may be separated from the language and each other by whitespace as well as commas
+/// (e.g. ` ```rust no_run `), and are always separated by just commas in the output. Line highlighting hints that some
+/// renderers support (e.g. ` ```rust {1,3} `) are removed, since rustdoc doesn't understand them.
+///
+/// Code blocks are found with a single pass over the lines of the readme, so large readmes don't slow down compilation.
+/// Fences may use backticks or tildes and be indented or inside blockquotes, and a block only ends at a fence of the
+/// same character that's at least as long as the opening one. Blocks that are never closed are left as-is. A line that
+/// looks like a block's closing fence, but has an info string (e.g. ```` ```rs ```` in a ```` ```rust ```` block),
+/// doesn't close the block (per the markdown spec), so it results in a warning pointing at the line.
+///
+/// Readmes with lone carriage return line breaks (`\r`) have them converted to line feeds, since rustdoc only splits
+/// code blocks into lines at line feeds and wouldn't be able to tell the hidden trailer apart from the other code.
+///
+/// # Options
+/// Additional behavior can be configured by passing `name = value` options after the three positional arguments.
+/// ```
+/// #[doc = pretty_readme::docify!(
+/// 	"README.md",
+/// 	"https://docs.rs/pretty-readme/latest/pretty_readme/",
+/// 	"./",
+/// 	strip_title = true,
+/// 	skip_blocks = [2],
+/// )]
+/// mod some_module {}
+/// ```
+///
+/// ## `trailers`
+/// List of `(language, trailer)` pairs that override the text appended to code blocks of each fence language.
+/// Rust code blocks (`rust` and `rs`, which are aliases of each other here) receive
+/// `# Ok::<(), Box<dyn std::error::Error>>(())` unless overridden. Blocks of other languages receive their trailer
+/// regardless of their contents, since the rules about `fn main`, `Ok(...)`, and the question mark operator only
+/// apply to Rust, e.g. `trailers = [("rust", "# Ok::<(), &str>(())")]`.
+///
+/// ## `template`
+/// Template to wrap the body of every code block that would otherwise receive a trailer in, instead of appending the
+/// trailer, for full control over the hidden boilerplate around examples. It must contain `{{body}}` exactly once,
+/// which is replaced with the block's code, e.g.
+/// `template = "# fn main() -> Result<(), MyError> {\n{{body}}\n# Ok(())\n# }"`.
+///
+/// ## `keep_original_as_title`
+/// When `true`, link destinations that contain the docs URL get the original absolute URL as their title (unless they
+/// already have one), so hovering over a relativized link still shows where it points. For example,
+/// `[Thing](https://docs.rs/some_crate/latest/some_crate/struct.Thing.html)` becomes
+/// `[Thing](./struct.Thing.html "https://docs.rs/some_crate/latest/some_crate/struct.Thing.html")`.
+///
+/// ## `relativize`
+/// Either `"url"` (the default) or `"intra_doc"`.
+/// `"intra_doc"` turns the destinations of links (inline ones and reference definitions) that point to an item's page
+/// under the docs URL into intra-doc link paths derived from the URL's structure, such as `crate::module::Thing` for
+/// `<docs_url>module/struct.Thing.html` or `crate::Thing::new` for `<docs_url>struct.Thing.html#method.new`, so the
+/// links resolve no matter where the docs are rendered. The docs URL should point to the crate's root module for this.
+/// Other occurrences of the docs URL are replaced as usual. The destination of a reference definition may also be on
+/// the line after its label, as markdown allows.
+///
+/// ## `dedent`
+/// When `true`, the longest common leading whitespace of all non-blank lines is removed before any other processing, so
+/// an indented title is still recognized by [`strip_title`](#strip_title). This is useful for readmes that are stored
+/// indented, such as ones extracted from a YAML literal block.
+///
+/// ## `html_tables`
+/// Either `"keep"` (the default) or `"strip"`.
+/// Raw HTML `<table>` elements render inconsistently across rustdoc themes, so `"strip"` removes them entirely.
+/// Tables in code are left alone, and a table without a closing tag is kept (along with the rest of the readme), with
+/// a warning.
+///
+/// ## `skip_blocks`
+/// List of 1-based indices of Rust code blocks that shouldn't receive a trailer, counting only the blocks of languages
+/// that have one. This allows controlling trailers without editing the readme, such as when it's generated by another
+/// tool. For example, `skip_blocks = [2, 4]` leaves the second and fourth blocks as they are.
+///
+/// ## `skip_blockquoted_code`
+/// When `true`, code blocks inside blockquotes (those with a `>` before their opening fence) don't receive a trailer,
+/// since quoted code is usually illustrative context rather than a runnable example.
+///
+/// ## `merge_blocks`
+/// When `true`, all Rust code blocks that would otherwise receive a trailer (or only lack one because they don't use
+/// the question mark operator) are combined into a single doctest, in document order, so later blocks can use items
+/// and variables defined in earlier ones. The doctest takes the place of the last block, with the code from the earlier
+/// blocks included as hidden lines, and the earlier blocks are marked as `ignore` so they don't run on their own.
+/// ````md
+/// ```rust,ignore
+/// let number: u8 = "42".parse()?;
+/// ```
+///
+/// Later on:
+///
+/// ```rust
+/// # let number: u8 = "42".parse()?;
+/// assert_eq!(number, 42);
+///
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+/// ````
+///
+/// ## `merge_adjacent_blocks`
+/// When `true`, blocks are [merged](#merge_blocks) the same way, but only within runs of blocks that have nothing but
+/// blank lines between them (the common "continued example" pattern). Blocks separated by prose are left independent.
+///
+/// ## `examples_as_sections`
+/// When `true`, every Rust code block that isn't nested in a list item or blockquote is moved into an `# Examples`
+/// section at the end of the docs, under a subheading with the text of the nearest preceding heading (or `Example N`,
+/// where `N` is the block's index, if there is none). This gives each example its own entry in rustdoc's sidebar.
+/// Trailers are added before the blocks are moved.
+///
+/// ## `known_items`
+/// List of item names (e.g. `"StuffDoer"` or `"module::Thing"`) that reference links may point to.
+/// When given, a warning is emitted for every reference link (`[Item]`, `[text][Item]`, etc.) that has no definition
+/// in the readme and isn't in the list, since rustdoc would fail to resolve it as an intra-doc link.
+/// Surrounding backticks are ignored when comparing (`` [`Item`] `` matches `"Item"`).
+///
+/// ## `escape_bare_brackets`
+/// When `true`, the brackets of bare `[text]` in prose that doesn't form a link (it has no definition in the readme,
+/// and isn't one of the [`known_items`](#known_items) if given) are escaped. GitHub renders them as literal brackets,
+/// but rustdoc would otherwise warn about them as unresolved intra-doc links. For example, `Values are [optional].`
+/// becomes `Values are \[optional\].`.
+///
+/// ## `footnotes`
+/// Either `"keep"` (the default), `"inline"`, or `"drop"`.
+/// `"inline"` replaces each GFM footnote reference (`[^label]`) with its footnote's text in parentheses, and `"drop"`
+/// removes the references. The footnote definitions are removed either way. For example, with `"inline"`,
+/// `The readme[^1] is read.` and `[^1]: Usually README.md.` become `The readme (Usually README.md.) is read.`.
+///
+/// ## `expect_doctests`
+/// Number of Rust code blocks without an `ignore` attribute that the readme must contain once transformed (so
+/// [merged](#merge_blocks) blocks count as one). A compile error is emitted if the count differs, which catches
+/// examples being accidentally deleted or ignored.
+///
+/// ## `target`
+/// Either `"docs"` (the default) or `"github"`.
+/// `"github"` does the inverse of the usual transformation, for generating a readme to show on GitHub from
+/// docs-flavored markdown (e.g. along with [`DOCIFY_EMIT_PATH`](#emitting-to-a-file)): lines hidden from rendered docs
+/// are removed from code blocks (and [`docify(hidden)`](#code-block-trailers) blocks entirely), and link destinations
+/// starting with the replacement are changed back to start with the docs URL. Most other options have no effect then.
+///
+/// ## `rename`
+/// Pair of the crate's old name and its new name (`("old-name", "new-name")`), for readmes that haven't caught up with
+/// a renamed crate. Whole-word occurrences of the old name in prose (outside of code) are replaced with the new one,
+/// leaving URLs (link destinations, autolinks and bare URLs) alone.
+/// With `rename_in_code = true`, paths starting with the old name (`old_name::...` and `extern crate old_name`) in Rust
+/// code blocks are also rewritten, with hyphens in the names turned into underscores.
+///
+/// ## `normalize_lang`
+/// When `true`, the language of every code block (the first token of its info string) is lowercased, e.g. ` ```Rust `
+/// becomes ` ```rust `. With `canonical_lang = true` (which can also be used on its own), ` ```rs ` (in any case)
+/// becomes ` ```rust ` as well.
+///
+/// ## `prelude`
+/// Code (usually `use` declarations) to add as hidden lines to the start of every Rust code block that would otherwise
+/// receive a trailer (or only lacks one because it doesn't use the question mark operator), so the readme's examples
+/// don't all need to repeat the same imports. Since not every block uses all of the imports, each `use` is preceded by
+/// a hidden `#[allow(unused_imports)]`, unless `allow_unused_prelude = false` is given.
+/// For example, `prelude = "use std::collections::HashMap;"` adds these lines to the start of each block:
+/// ```md
+/// # #[allow(unused_imports)]
+/// # use std::collections::HashMap;
+/// ```
+///
+/// ## `import_items`
+/// Path of the crate (e.g. `"some_crate"`) to import the [known items](#known_items) that the readme's reference links
+/// refer to from. A hidden `use` declaration is added to the start of every block that would
+/// [receive the prelude](#prelude) and refers to one of those items by name without importing or defining it itself, so
+/// the examples run without visible imports. Items that share a name with another referenced item aren't imported, with
+/// a warning, since it's unclear which of them is meant. Without `known_items`, nothing is imported.
+///
+/// ## `by_text`
+/// Pair of link text and a destination (`("docs", "./")`). The destination of every link whose text matches (compared
+/// case-insensitively) is set to the given one, regardless of where it pointed before. Reference links to a definition
+/// in the readme are turned into inline links, leaving the definition as-is.
+///
+/// ## `dedupe_references`
+/// When `true`, reference definitions with the same destination and title (e.g. once the docs URL has been replaced)
+/// are merged after all other link rewriting: only the first of them is kept, and references to the others are pointed
+/// at it instead.
+///
+/// ## `strip_title`
+/// When `true`, the readme's title (its first heading, if it's a level 1 heading at the very start) is removed, since
+/// rustdoc already shows the crate's name. Both ATX (`# Title`) and setext (`Title` underlined with `===`) headings are
+/// recognized. Anchor links (`[text](#anchor)`) to headings that are no longer in the readme once its title is removed
+/// result in a warning.
+///
+/// ## `section`
+/// Text of a heading (compared case-insensitively) whose section should be used instead of the whole readme. The
+/// section runs up to the next heading of the same or a higher level, and doesn't include its own heading. Setext
+/// headings count as level 1 (`===`) or 2 (`---`), and a `---` line only underlines a heading if it directly follows
+/// paragraph text (it's a thematic break otherwise). It's an error for no heading to match, e.g. for
+/// `section = "Usage"` in a readme without a `## Usage` heading.
+///
+/// ## `version_footer`
+/// When `true`, a footer noting the crate version the docs were generated for (`*Docs generated for v1.2.3*`) is
+/// appended, after a thematic break. If the `DOCIFY_COMMIT` environment variable is set (e.g. by a build script), the
+/// commit hash it contains is included as well (`*Docs generated for v1.2.3 (commit `abc1234`)*`).
+///
+/// ## `replacement_debug` and `replacement_release`
+/// Replacements for the docs URL to use instead of the one given, in debug and release builds respectively, e.g. so
+/// that docs built locally link to a local path while the ones on docs.rs link to its URLs. Each may contain
+/// [manifest placeholders](#manifest-placeholders) as well.
+/// The build profile is taken from the `PROFILE` environment variable (`release` for release builds), falling back to
+/// `DEBUG` (`false` for release builds). Cargo only sets these for build scripts, so they need to be set when invoking
+/// it (e.g. `PROFILE=release cargo doc`); when neither is set, the replacement given as an argument is used.
+/// ```
+/// # let docs =
+/// pretty_readme::docify!(
+/// 	"README.md",
+/// 	"https://docs.rs/pretty-readme/latest/pretty_readme/",
+/// 	"./",
+/// 	replacement_debug = "file:///home/me/pretty-readme/target/doc/pretty_readme/",
+/// 	replacement_release = "https://docs.rs/pretty-readme/latest/pretty_readme/",
+/// );
+/// ```
+///
+/// ## `strict`
+/// When `true`, warnings are turned into compile errors.
+///
+/// ## Defaults
+/// Options shared by many invocations (e.g. across a workspace) can be given in the `DOCIFY_DEFAULT_OPTS` environment
+/// variable (which can be set with `cargo:rustc-env` in a build script, or in `.cargo/config.toml`) using the same
+/// syntax, e.g. `DOCIFY_DEFAULT_OPTS='dedent = true, strict = true'`.
+/// Options given to the macro itself take precedence over the defaults.
+///
+/// # Included regions
+/// If the readme contains any `<!-- docify:start -->` and `<!-- docify:end -->` markers, only the regions between them
+/// are used (e.g. to leave out badges or a table of contents that only make sense on GitHub). Multiple regions are
+/// concatenated in document order, separated by a blank line. A start marker that's never closed (even after other
+/// complete regions) includes the rest of the readme, with a warning.
+/// ```md
+/// [![Badge](https://example.com/badge.svg)](https://example.com)
+/// <!-- docify:start -->
+/// Only this is included.
+/// <!-- docify:end -->
+/// ```
+///
+/// # Replacing the docs URL
+/// The replacement may be empty, in which case the docs URL is simply removed wherever it appears. This turns absolute
+/// links to docs items into relative ones, even in strict mode. The docs URL itself can't be empty (including once any
+/// [manifest placeholders](#manifest-placeholders) in it have been expanded), since there would be nothing to replace.
+///
+/// # No-replace regions
+/// The docs URL isn't replaced anywhere between `<!-- docify:noreplace -->` and `<!-- /docify:noreplace -->` markers,
+/// which is useful for sections (like a changelog) that need to keep their absolute links. Markers in code don't count,
+/// and an opening marker without a closing one protects the rest of the readme, with a warning.
+/// ```md
+/// <!-- docify:noreplace -->
+/// - v1.0.0: added [`Thing`](https://docs.rs/some_crate/1.0.0/struct.Thing.html)
+/// <!-- /docify:noreplace -->
+/// ```
+///
+/// HTML `<code>` elements are left as-is as well, while the `href` and `src` attributes of HTML links and images are
+/// treated as link destinations (so they also get a title with [`keep_original_as_title`](#keep_original_as_title)).
+///
+/// # Manifest placeholders
+/// The docs URL and its replacement may contain `{{homepage}}` and `{{documentation}}` placeholders, which are replaced
+/// with the values of the corresponding fields in the crate's manifest so the URLs track it automatically.
+/// Using a placeholder for a field that isn't set is an error. For example, with this in the manifest:
+/// ```toml
+/// [package]
+/// documentation = "https://docs.rs/some_crate"
+/// ```
+/// the docs URL below is `https://docs.rs/some_crate/latest/some_crate/`:
+/// ```rust,ignore
+/// pretty_readme::docify!("README.md", "{{documentation}}/latest/some_crate/", "./");
+/// ```
+///
+/// The readme itself may contain a `{{features}}` placeholder (outside of code), which is replaced with a bullet list
+/// of the crate features enabled for the build, according to the `CARGO_FEATURE_<NAME>` environment variables. Since
+/// those names are uppercased with hyphens turned into underscores, they're matched up with the features in the
+/// manifest to get their original spelling (e.g. `async-io` for `CARGO_FEATURE_ASYNC_IO`), or just lowercased if
+/// there's no match. Cargo only sets the variables for build scripts, so a build script needs to pass them on to the
+/// crate with `cargo:rustc-env=CARGO_FEATURE_<NAME>=1` for them to be seen.
+///
+/// # Manifest replacements
+/// Further pairs of docs URLs and their replacements can be listed in the crate's manifest, in a
+/// `[package.metadata.docify.replacements]` table of `"<docs_url>" = "<replacement>"` entries (which may contain
+/// placeholders as well). They're applied after the docs URL given to the macro, and allow leaving that out entirely to
+/// only give the readme path, which keeps the link configuration for every invocation in one place.
+/// ```toml
+/// [package.metadata.docify.replacements]
+/// "https://docs.rs/some_crate/latest/some_crate/" = "./"
+/// "https://github.com/Gawdl3y/some_crate/blob/main/" = "{{homepage}}/blob/main/"
+/// ```
+/// With a table like that, `docify!("README.md")` turns `https://docs.rs/some_crate/latest/some_crate/` into `./`
+/// throughout the readme. Without one, the docs URL and replacement must be given.
+///
+/// # Readme path
+/// The readme path is resolved relative to Cargo.toml, unless it starts with `~`, in which case it is resolved relative
+/// to the user's home directory (`HOME`, or `USERPROFILE` on Windows) instead.
+/// When the macro isn't invoked by cargo (so `CARGO_MANIFEST_DIR` isn't set), the current directory is used in place of
+/// the directory containing Cargo.toml.
+/// This can be useful for sharing a single readme between many repositories, e.g. `~/shared/README.md`.
+///
+/// # Output
+/// The result is emitted as a string literal with everything that needs it escaped, so it's always a valid `#[doc]`
+/// value no matter what the readme contains, and its value is exactly the transformed readme. Backslashes, quotes, and
+/// text that looks like escape sequences or doc comments come through as-is.
+///
+/// NUL characters are replaced with U+FFFD, as the markdown spec requires. Unicode text direction controls (U+202A to
+/// U+202E and U+2066 to U+2069), which can make text display in a different order than it's stored, are replaced with
+/// HTML character references outside of code, which rustdoc renders the same.
+/// They're left as they are in code, with a warning, since rustc rejects them in doctests by default.
+///
+/// # Emitting to a file
+/// When the `DOCIFY_EMIT_PATH` environment variable is set, the transformed readme is also written to the file at that
+/// path (relative to Cargo.toml), overwriting it. The generated file can then be included with
+/// `#[doc = include_str!(...)]` instead of invoking the macro at the doc site, e.g. to keep IDE analysis fast.
+/// Only set it for builds with a single invocation of the macro, since each invocation writes to the same file.
+///
+/// # Reports
+/// When the `DOCIFY_REPORT` environment variable is set, a JSON report of the transformation is written to the file at
+/// that path (relative to Cargo.toml), for use by tooling and CI dashboards. It includes the readme path, the options
+/// given, the code blocks found along with what happened to each (and why any were skipped), and each docs URL with its
+/// replacement and the number of occurrences replaced. As with `DOCIFY_EMIT_PATH`, each invocation overwrites the file.
+///
+/// # Scraped examples
+/// Rustdoc's `--scrape-examples` option only finds example code in a crate's example targets, not in its docs. When the
+/// `DOCIFY_EXAMPLES_DIR` environment variable is set, each Rust code block that runs as a doctest is also written to
+/// its own file in the directory at that path (relative to Cargo.toml), as a standalone program in the same form
+/// rustdoc tests it in: hidden lines are revealed, and blocks without a `main` function are wrapped in one. The files
+/// are named after the readme and the block's position among the doctests (e.g. `readme_1.rs`), so pointing the
+/// variable at `examples/` lets cargo discover them as examples to scrape. Each invocation overwrites its files.
+///
 /// [crate documentation]: crate
 #[proc_macro]
 #[allow(clippy::missing_panics_doc)]
 pub fn docify(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
-	let input = proc_macro2::TokenStream::from(input);
-	let input_span = input.span();
+	let Input {
+		path,
+		docs_url,
+		replacement,
+		options,
+	} = syn::parse_macro_input!(input as Input);
 
-	// Extract each parameter from the input tokens
-	let args = match Args::parse_terminated.parse(input.into()) {
-		Ok(args) => Vec::from_iter(args),
+	let original = match read_readme(&path) {
+		Ok(readme) => readme,
 		Err(err) => return err.into_compile_error().into(),
 	};
-	let (path, text, replacement) = match args.as_slice() {
-		[path, text, replacement] => (path, text.value(), replacement.value()),
-		_ => {
-			return syn::Error::new(
-				input_span,
-				r#"expected `"<readme_path>", "<docs_url>", "<replacement_docs_url>"`"#,
-			)
-			.into_compile_error()
-			.into()
-		}
+	let replacements = match replacements(&path, docs_url.as_ref(), replacement.as_ref(), &options, |name| {
+		env::var_os(name)
+	}) {
+		Ok(replacements) => replacements,
+		Err(err) => return err.into_compile_error().into(),
+	};
+
+	let mut diagnostics = Diagnostics::from_env();
+	let mut report = Report::default();
+
+	// Remove any indentation common to the whole document before anything else looks at it
+	let readme = if options.dedent {
+		document::dedent(&original, &codeblocks::verbatim_ranges(&original))
+	} else {
+		original.clone()
 	};
 
-	// Resolve the readme path
-	let project_root = env::var("CARGO_MANIFEST_DIR").unwrap_or(".".to_owned());
-	let readme_path = Path::new(&project_root).join(path.value());
+	// Only keep the marked regions of the readme, if there are any
+	let readme = document::extract_regions(&readme, &mut diagnostics);
 
-	// Read the contents of the readme
-	let readme = if readme_path.is_file() {
-		match fs::read_to_string(&readme_path) {
-			Ok(contents) => contents,
-			Err(err) => {
-				return syn::Error::new_spanned(path, format!("Error reading readme file at {readme_path:?}: {err}"))
+	// Only keep the desired section, or remove the title, if desired
+	let readme = match &options.section {
+		Some(heading) => match document::extract_section(&readme, heading) {
+			Some(section) => section,
+			None => {
+				return syn::Error::new_spanned(&path, format!("Readme has no section with the heading `{heading}`"))
 					.into_compile_error()
 					.into()
 			}
+		},
+		None if options.strip_title => document::strip_title(&readme),
+		None => readme,
+	};
+
+	// Transform the readme for the desired target
+	let readme = match options.target {
+		Target::Docs => transform_for_docs(&readme, &replacements, &options, &mut diagnostics, &mut report),
+		Target::Github => transform_for_github(&readme, &replacements, &options),
+	};
+
+	// Make sure nothing in the result can trip up rustdoc or other tools
+	let readme = document::sanitize(&readme, &mut diagnostics);
+
+	// Check for closing fences that don't actually close their blocks
+	codeblocks::check_closing_fences(&original, &mut diagnostics);
+
+	// Check that anchor links to headings still resolve after headings have been removed
+	links::check_anchors(&original, &readme, &mut diagnostics);
+
+	// List the languages of the code blocks that remain, and which of them are run
+	codeblocks::note_languages(&readme, &mut diagnostics);
+
+	// Make sure the expected number of doctests remain
+	if let Some(expected) = options.expect_doctests {
+		let count = codeblocks::count_doctests(&readme);
+		if count != expected {
+			return syn::Error::new_spanned(
+				&path,
+				format!("expected the readme to contain {expected} doctests, but found {count}"),
+			)
+			.into_compile_error()
+			.into();
 		}
+	}
+
+	if let Err(err) = diagnostics.emit(&path, options.strict) {
+		return err.into_compile_error().into();
+	}
+
+	// Write the result and the report to files as well if desired
+	let report = || report.into_json(&path.value(), &options.given);
+	let var = |name: &str| env::var_os(name);
+	if let Err(err) = write_to_file(&path, EMIT_PATH_VAR, var, "transformed readme", || readme.clone())
+		.and_then(|()| write_to_file(&path, REPORT_VAR, var, "report", report))
+		.and_then(|()| write_examples(&path, var, &readme))
+	{
+		return err.into_compile_error().into();
+	}
+	// Quotes, backslashes, and control characters are escaped in the literal, so its value is exactly the result
+	LitStr::new(&readme, path.span()).into_token_stream().into()
+}
+
+/// Defines a `macro_rules!` macro with the given name that invokes [`docify!`] with a shared configuration, so that the
+/// docs URL, its replacement, and any options don't need to be repeated across many doc sites in one crate.
+/// The docs URL and replacement are given as `docs: "..."` and `rel: "..."`, followed by any options as `name: value`.
+/// The defined macro then takes a readme path (relative to Cargo.toml), optionally followed by further options in the
+/// usual `name = value` syntax (which override the configured ones), and is used either directly or via
+/// [`docify_with!`]. Since macros defined this way are only available after their definition, the configuration must
+/// come before any doc sites that use it.
+///
+/// # Examples
+/// ```
+/// pretty_readme::docify_config! {
+/// 	readme_docs,
+/// 	docs: "https://docs.rs/some_crate/latest/some_crate/",
+/// 	rel: "./",
+/// 	strip_title: true,
+/// }
+///
+/// #[doc = readme_docs!("tests/fixtures/config.md")]
+/// mod some_module {}
+///
+/// #[doc = readme_docs!("tests/fixtures/config.md", strip_title = false)]
+/// mod titled_module {}
+/// ```
+///
+/// Any invalid options are reported at the configuration rather than at each use of it.
+/// ```compile_fail
+/// pretty_readme::docify_config! {
+/// 	readme_docs,
+/// 	docs: "https://docs.rs/some_crate/latest/some_crate/",
+/// 	rel: "./",
+/// 	strip_title: "yes",
+/// }
+/// ```
+#[proc_macro]
+pub fn docify_config(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+	syn::parse_macro_input!(input as Config).into_macro().into()
+}
+
+/// Invokes a macro defined with [`docify_config!`] for the given readme path, along with any further options.
+/// `docify_with!(readme_docs, "README.md")` is equivalent to `readme_docs!("README.md")`.
+#[proc_macro]
+pub fn docify_with(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+	let Invocation { name, args } = syn::parse_macro_input!(input as Invocation);
+	quote::quote!(#name!(#args)).into()
+}
+
+/// Transforms the readme for rustdoc: appending trailers to code blocks, replacing the docs URL, and so on
+fn transform_for_docs(
+	readme: &str,
+	replacements: &[(String, String)],
+	options: &Options,
+	diagnostics: &mut Diagnostics,
+	report: &mut Report,
+) -> String {
+	// Turn lone carriage returns into line feeds, so that rustdoc sees the lines of code blocks (and their trailers)
+	let readme = document::normalize_line_breaks(readme, &codeblocks::verbatim_ranges(readme));
+
+	// Fill in the list of enabled features
+	let readme = document::expand_features(&readme, document::enabled_features());
+
+	// Strip HTML tables if desired
+	let readme = match options.html_tables {
+		HtmlTables::Keep => readme,
+		HtmlTables::Strip => document::strip_html_tables(&readme, diagnostics),
+	};
+
+	// Inline or drop footnotes if desired
+	let readme = document::convert_footnotes(&readme, options.footnotes);
+
+	// Replace the crate's old name if desired
+	let readme = match &options.rename {
+		Some((old, new)) => document::rename_crate(&readme, old, new, options.rename_in_code),
+		None => readme,
+	};
+
+	// Normalize the languages of code blocks if desired
+	let readme = if options.normalize_lang || options.canonical_lang {
+		document::normalize_langs(&readme, options.normalize_lang, options.canonical_lang)
+	} else {
+		readme
+	};
+
+	// Append the trailers to the end of all code blocks that have one
+	let readme = codeblocks::add_trailers(&readme, options, diagnostics, report);
+
+	// Move the Rust code blocks into their own examples section if desired
+	let readme = if options.examples_as_sections {
+		codeblocks::examples_as_sections(&readme)
+	} else {
+		readme
+	};
+
+	// Replace each docs URL with its replacement
+	let mut readme = readme;
+	for (docs_url, replacement) in replacements {
+		let replaced = links::replace_docs_url(&readme, docs_url, replacement, options, diagnostics);
+		let count = readme
+			.matches(docs_url.as_str())
+			.count()
+			.saturating_sub(replaced.matches(docs_url.as_str()).count());
+		report.replacement(docs_url, replacement, count);
+		readme = replaced;
+	}
+
+	// Repoint links with the given text if desired
+	let readme = match &options.by_text {
+		Some((text, dest)) => links::retarget_by_text(&readme, text, dest),
+		None => readme,
+	};
+
+	// Merge reference definitions that now point to the same place if desired
+	let readme = if options.dedupe_references {
+		links::dedupe_references(&readme)
+	} else {
+		readme
+	};
+
+	// Escape brackets that don't form links
+	let readme = if options.escape_bare_brackets {
+		links::escape_bare_brackets(&readme, options.known_items.as_ref())
 	} else {
-		return syn::Error::new_spanned(
+		readme
+	};
+
+	// Note which version the docs are for if desired
+	let readme = if options.version_footer {
+		document::append_version_footer(&readme, |name| env::var_os(name))
+	} else {
+		readme
+	};
+
+	// Check that all reference links will resolve
+	if let Some(known_items) = &options.known_items {
+		links::check_references(&readme, known_items, diagnostics);
+	}
+
+	readme
+}
+
+/// Transforms the readme for GitHub (or wherever else the readme is shown), reversing what
+/// [`transform_for_docs`] would do: removing hidden lines from code blocks and turning relative links back into
+/// absolute ones
+fn transform_for_github(readme: &str, replacements: &[(String, String)], options: &Options) -> String {
+	let readme = codeblocks::strip_hidden_lines(readme, options);
+	replacements.iter().fold(readme, |readme, (docs_url, replacement)| {
+		links::absolutize(&readme, replacement, docs_url)
+	})
+}
+
+/// Gathers the pairs of docs URLs and their replacements to apply: the ones given to the macro (if any, with the
+/// replacement for the current [build profile](Options::profile_replacement) taking precedence), followed by the ones
+/// in the manifest's replacements table. Placeholders in them are expanded, and it's an error for there to be none or
+/// for any docs URL to be empty. Environment variables are looked up with the given function.
+fn replacements(
+	path: &LitStr,
+	docs_url: Option<&LitStr>,
+	replacement: Option<&LitStr>,
+	options: &Options,
+	var: impl Fn(&str) -> Option<OsString>,
+) -> syn::Result<Vec<(String, String)>> {
+	let mut replacements = Vec::new();
+	if let (Some(docs_url), Some(replacement)) = (docs_url, replacement) {
+		let expanded = manifest::expand_placeholders(docs_url, &var)?;
+		if expanded.is_empty() {
+			return Err(syn::Error::new_spanned(
+				docs_url,
+				"the docs URL to replace can't be empty",
+			));
+		}
+		replacements.push((
+			expanded,
+			manifest::expand_placeholders(options.profile_replacement(&var).unwrap_or(replacement), &var)?,
+		));
+	}
+
+	for (docs_url, replacement) in manifest::replacements(path, &var)? {
+		if docs_url.is_empty() {
+			return Err(syn::Error::new_spanned(
+				path,
+				"the manifest's docify replacements table has an empty docs URL to replace",
+			));
+		}
+		replacements.push((docs_url, replacement));
+	}
+
+	if replacements.is_empty() {
+		return Err(syn::Error::new_spanned(
 			path,
-			format!("Readme file at {readme_path:?} not found; path must be relative to Cargo.toml"),
-		)
-		.into_compile_error()
-		.into();
+			"no docs URL was given, and the manifest has no `[package.metadata.docify.replacements]` table entries",
+		));
+	}
+	Ok(replacements)
+}
+
+/// Writes the contents produced by the given function to the file at the path given by an environment variable, if it's
+/// set. Relative paths are resolved relative to Cargo.toml (or the current directory, if `CARGO_MANIFEST_DIR` isn't
+/// set). Environment variables are looked up with the given function.
+fn write_to_file(
+	path: &LitStr,
+	name: &str,
+	var: impl Fn(&str) -> Option<OsString>,
+	what: &str,
+	contents: impl FnOnce() -> String,
+) -> syn::Result<()> {
+	let Some(emit_path) = var(name).filter(|value| !value.is_empty()) else {
+		return Ok(());
 	};
+	let emit_path = var("CARGO_MANIFEST_DIR")
+		.map_or_else(|| PathBuf::from("."), PathBuf::from)
+		.join(emit_path);
+
+	emit_path
+		.parent()
+		.map_or(Ok(()), fs::create_dir_all)
+		.and_then(|()| fs::write(&emit_path, contents()))
+		.map_err(|err| syn::Error::new_spanned(path, format!("Error writing {what} to {}: {err}", emit_path.display())))
+}
+
+/// Writes each of the readme's doctests to its own file in the directory given by [`EXAMPLES_DIR_VAR`], if it's set.
+/// The files are named after the readme and the doctest's 1-based index, e.g. `readme_1.rs` for `README.md`.
+/// As with [`write_to_file`], a relative directory is resolved relative to Cargo.toml, and environment variables are
+/// looked up with the given function.
+fn write_examples(path: &LitStr, var: impl Fn(&str) -> Option<OsString>, readme: &str) -> syn::Result<()> {
+	let Some(dir) = var(EXAMPLES_DIR_VAR).filter(|value| !value.is_empty()) else {
+		return Ok(());
+	};
+	let dir = var("CARGO_MANIFEST_DIR")
+		.map_or_else(|| PathBuf::from("."), PathBuf::from)
+		.join(dir);
+
+	let value = path.value();
+	let stem = Path::new(&value)
+		.file_stem()
+		.map(|stem| stem.to_string_lossy().to_lowercase())
+		.unwrap_or_default()
+		.replace(|c: char| !c.is_ascii_alphanumeric(), "_");
+
+	fs::create_dir_all(&dir)
+		.and_then(|()| {
+			codeblocks::scraped_examples(readme)
+				.into_iter()
+				.enumerate()
+				.try_for_each(|(idx, example)| {
+					fs::write(dir.join(format!("{stem}_{}.rs", idx.saturating_add(1))), example)
+				})
+		})
+		.map_err(|err| syn::Error::new_spanned(path, format!("Error writing examples to {}: {err}", dir.display())))
+}
+
+/// Resolves the given readme path and reads the contents of the file
+fn read_readme(path: &LitStr) -> syn::Result<String> {
+	let (readme_path, hint) = resolve_readme_path(path, |name| env::var_os(name))?;
+
+	if readme_path.is_file() {
+		fs::read_to_string(&readme_path).map_err(|err| {
+			syn::Error::new_spanned(
+				path,
+				format!("Error reading readme file at {}: {err}", readme_path.display()),
+			)
+		})
+	} else {
+		Err(syn::Error::new_spanned(
+			path,
+			format!("Readme file at {} not found; {hint}", readme_path.display()),
+		))
+	}
+}
+
+/// Resolves the given readme path to a full path, along with a hint about what it was resolved relative to.
+/// Paths starting with `~` are resolved relative to the user's home directory, and all others relative to Cargo.toml
+/// (or the current directory, if `CARGO_MANIFEST_DIR` isn't set because the macro isn't being run by cargo).
+/// Environment variables are looked up with the given function.
+fn resolve_readme_path(path: &LitStr, var: impl Fn(&str) -> Option<OsString>) -> syn::Result<(PathBuf, &'static str)> {
+	let value = path.value();
+
+	if let Some(rest) = value.strip_prefix('~') {
+		if rest.is_empty() || rest.starts_with(['/', '\\']) {
+			let non_empty = |home: &OsString| !home.is_empty();
+			let home = var("HOME")
+				.filter(non_empty)
+				.or_else(|| var("USERPROFILE").filter(non_empty))
+				.ok_or_else(|| {
+					syn::Error::new_spanned(
+						path,
+						"Unable to resolve `~` in readme path: home directory could not be determined (neither HOME \
+						 nor USERPROFILE is set)",
+					)
+				})?;
+			return Ok((
+				Path::new(&home).join(rest.trim_start_matches(['/', '\\'])),
+				"path must be relative to the home directory",
+			));
+		}
+	}
+
+	match var("CARGO_MANIFEST_DIR") {
+		Some(project_root) => Ok((
+			Path::new(&project_root).join(value),
+			"path must be relative to Cargo.toml",
+		)),
+		None => Ok((
+			Path::new(".").join(value),
+			"CARGO_MANIFEST_DIR is not set (the macro doesn't seem to be invoked by cargo), so the path was resolved \
+			 relative to the current directory",
+		)),
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	//! Tests for the files the macro reads from and writes to
+
+	use std::process;
+
+	use proc_macro2::Span;
+
+	use super::*;
+	use crate::test_util::vars;
+
+	/// Resolves the given readme path with the given variables set, panicking if it can't be resolved
+	fn resolve(path: &str, env: &[(&str, &str)]) -> (PathBuf, &'static str) {
+		resolve_readme_path(&LitStr::new(path, Span::call_site()), vars(env)).unwrap()
+	}
+
+	/// `~` on its own or followed by a separator is the home directory, from `HOME` or else (if it's unset or empty)
+	/// `USERPROFILE`
+	#[test]
+	fn resolve_readme_path_expands_home() {
+		let env = [("HOME", "/home/user"), ("CARGO_MANIFEST_DIR", "/project")];
+		assert_eq!(resolve("~", &env).0, Path::new("/home/user"));
+		assert_eq!(
+			resolve("~/docs/README.md", &env).0,
+			Path::new("/home/user/docs/README.md")
+		);
+		assert_eq!(
+			resolve("~/README.md", &[("USERPROFILE", "/users/user")]).0,
+			Path::new("/users/user/README.md")
+		);
+		assert_eq!(
+			resolve("~/README.md", &[("HOME", ""), ("USERPROFILE", "/users/user")]).0,
+			Path::new("/users/user/README.md")
+		);
+		assert_eq!(
+			resolve("~user/README.md", &env).0,
+			Path::new("/project/~user/README.md")
+		);
+	}
+
+	/// Other paths are relative to Cargo.toml, or to the current directory with a hint saying so when the macro isn't
+	/// being run by cargo
+	#[test]
+	fn resolve_readme_path_without_manifest_dir() {
+		assert_eq!(
+			resolve("README.md", &[("CARGO_MANIFEST_DIR", "/project")]),
+			(
+				PathBuf::from("/project/README.md"),
+				"path must be relative to Cargo.toml"
+			)
+		);
+
+		let (path, hint) = resolve("README.md", &[]);
+		assert_eq!(path, Path::new("./README.md"));
+		assert!(hint.starts_with("CARGO_MANIFEST_DIR is not set"));
+		assert!(hint.ends_with("resolved relative to the current directory"));
+	}
+
+	/// The contents are written to the file at the path in the variable, creating its directory, and nothing is
+	/// written when the variable isn't set
+	#[test]
+	fn write_to_file_writes_contents() {
+		let dir = env::temp_dir().join(format!("docify-write-to-file-{}", process::id()));
+		let emit_path = dir.join("nested").join("README.md");
+		let path = LitStr::new("README.md", Span::call_site());
+		let readme = "# Some crate\n\nSome text.\n";
+
+		let env = [(EMIT_PATH_VAR, emit_path.to_str().unwrap())];
+		write_to_file(&path, EMIT_PATH_VAR, vars(&env), "transformed readme", || {
+			readme.to_owned()
+		})
+		.unwrap();
+		assert_eq!(fs::read_to_string(&emit_path).unwrap(), readme);
 
-	// Insert "# Ok::<(), Box<dyn std::error::Error>>(())" at the end of all Rust codeblocks
-	let re = RegexBuilder::new(r"```(rust|rs)(\r\n|\r|\n)(.+?)(\r\n|\r|\n)```")
-		.dot_matches_new_line(true)
-		.case_insensitive(true)
-		.build()
-		.expect("unable to build codeblock regex");
-	let readme = re.replace_all(&readme, "```$1$2$3$4$4# Ok::<(), Box<dyn std::error::Error>>(())$4```");
+		write_to_file(&path, EMIT_PATH_VAR, vars(&[]), "transformed readme", || unreachable!()).unwrap();
+		fs::remove_dir_all(&dir).unwrap();
+	}
 
-	// Replace the given docs URL with the given replacement
-	readme.replace(&text, &replacement).into_token_stream().into()
+	/// Examples are written to the directory in the variable, relative to the manifest directory, and nothing is
+	/// written when the variable isn't set
+	#[test]
+	fn write_examples_resolves_directory() {
+		let manifest_dir = env::temp_dir().join(format!("docify-write-examples-{}", process::id()));
+		let path = LitStr::new("Some-README.md", Span::call_site());
+		let readme = "```rust\nlet first = 1;\n```\n\n```text\nnot code\n```\n\n\
+			```rust\nlet second = 2;\n```\n";
+
+		let env = [
+			(EXAMPLES_DIR_VAR, "examples"),
+			("CARGO_MANIFEST_DIR", manifest_dir.to_str().unwrap()),
+		];
+		write_examples(&path, vars(&env), readme).unwrap();
+		let mut files = fs::read_dir(manifest_dir.join("examples"))
+			.unwrap()
+			.map(|entry| entry.unwrap().file_name())
+			.collect::<Vec<_>>();
+		files.sort();
+		assert_eq!(files, ["some_readme_1.rs", "some_readme_2.rs"]);
+		assert!(
+			fs::read_to_string(manifest_dir.join("examples").join("some_readme_2.rs"))
+				.unwrap()
+				.contains("let second = 2;")
+		);
+
+		write_examples(&path, vars(&[(EXAMPLES_DIR_VAR, "")]), readme).unwrap();
+		write_examples(&path, vars(&[]), readme).unwrap();
+		fs::remove_dir_all(&manifest_dir).unwrap();
+	}
+
+	/// Docs URL used in the fixtures
+	const LATEST_URL: &str = "https://docs.rs/some_crate/latest/some_crate/";
+
+	/// Rustdoc output gets trailers and relative links, and GitHub output is turned back into what it would look like
+	/// without them
+	#[test]
+	fn transform_targets() {
+		let readme = include_str!("../tests/fixtures/target.md");
+		let replacements = [(LATEST_URL.to_owned(), "./".to_owned())];
+		let options = Options::default();
+		assert_eq!(
+			transform_for_docs(
+				readme,
+				&replacements,
+				&options,
+				&mut Diagnostics::new(false),
+				&mut Report::default()
+			),
+			"See [`Thing`](./struct.Thing.html) and [the docs].\n\n```rust\n# use some_crate::Thing;\n\
+			 let thing = Thing::new()?;\n\n# Ok::<(), Box<dyn std::error::Error>>(())\n```\n\n\
+			 [the docs]: ./index.html\n"
+		);
+		assert_eq!(
+			transform_for_github(readme, &replacements, &options),
+			"See [`Thing`](https://docs.rs/some_crate/latest/some_crate/struct.Thing.html) and [the docs].\n\n\
+			 ```rust\nlet thing = Thing::new()?;\n```\n\n\
+			 [the docs]: https://docs.rs/some_crate/latest/some_crate/index.html\n"
+		);
+	}
+
+	/// Each pair of docs URL and replacement is reported with its own count
+	#[test]
+	fn transform_for_docs_reports_each_replacement() {
+		let replacements = [
+			("https://docs.rs/a/".to_owned(), "./".to_owned()),
+			("https://docs.rs/b/".to_owned(), "../b/".to_owned()),
+		];
+		let mut report = Report::default();
+		transform_for_docs(
+			"See [a](https://docs.rs/a/x.html), [b](https://docs.rs/b/y.html) and [c](https://docs.rs/b/z.html).\n",
+			&replacements,
+			&Options::default(),
+			&mut Diagnostics::new(false),
+			&mut report,
+		);
+		assert!(report.into_json("README.md", &[]).ends_with(
+			"\t\"replacements\": [{\"docs_url\": \"https://docs.rs/a/\", \"replacement\": \"./\", \"count\": 1}, \
+			 {\"docs_url\": \"https://docs.rs/b/\", \"replacement\": \"../b/\", \"count\": 2}]\n}\n"
+		));
+	}
+
+	/// There must be a non-empty docs URL to replace, either given or in the manifest
+	#[test]
+	fn replacements_require_docs_url() {
+		let path = LitStr::new("README.md", Span::call_site());
+		let empty = LitStr::new("", Span::call_site());
+		let options = Options::default();
+
+		let err = replacements(&path, Some(&empty), Some(&empty), &options, vars(&[])).unwrap_err();
+		assert_eq!(err.to_string(), "the docs URL to replace can't be empty");
+
+		let err = replacements(&path, None, None, &options, vars(&[])).unwrap_err();
+		assert!(err.to_string().starts_with("no docs URL was given"));
+	}
+
+	/// The emitted literal's value is exactly the readme, whatever it contains
+	#[test]
+	fn output_literal_round_trips() {
+		let readme = include_str!("../tests/fixtures/tricky.md");
+		let tokens = LitStr::new(readme, Span::call_site()).into_token_stream();
+		assert_eq!(syn::parse2::<LitStr>(tokens).unwrap().value(), readme);
+	}
+
+	/// A `~` path is an error rather than a guess when there's no home directory to resolve it against
+	#[test]
+	fn resolve_readme_path_requires_home() {
+		let err = resolve_readme_path(
+			&LitStr::new("~/README.md", Span::call_site()),
+			vars(&[("HOME", ""), ("CARGO_MANIFEST_DIR", "/project")]),
+		)
+		.unwrap_err();
+		assert!(err.to_string().contains("home directory could not be determined"));
+	}
 }
@@ -0,0 +1,828 @@
+//! Replacement of docs URLs in the readme's links and text
+
+use std::{
+	collections::{BTreeMap, BTreeSet},
+	ops::Range,
+};
+
+use regex::{Captures, Regex, RegexBuilder};
+
+use crate::{
+	diagnostics::Diagnostics,
+	markdown,
+	options::{Options, Relativize},
+};
+
+/// Kinds of item pages rustdoc generates (as in `struct.Thing.html`)
+const ITEM_KINDS: [&str; 13] = [
+	"struct",
+	"enum",
+	"union",
+	"trait",
+	"traitalias",
+	"fn",
+	"type",
+	"macro",
+	"attr",
+	"derive",
+	"constant",
+	"static",
+	"primitive",
+];
+
+/// Kinds of item members rustdoc generates anchors for on item pages (as in `#method.new`)
+const MEMBER_KINDS: [&str; 7] = [
+	"method",
+	"tymethod",
+	"variant",
+	"structfield",
+	"associatedtype",
+	"associatedconstant",
+	"field",
+];
+
+/// Replaces all occurrences of the docs URL with its replacement, except for inside of no-replace regions
+/// (`<!-- docify:noreplace -->` ... `<!-- /docify:noreplace -->`) and HTML `<code>` elements.
+/// If [`Options::keep_original_as_title`] is set, rewritten link destinations also get the original URL as their title.
+pub(crate) fn replace_docs_url(
+	readme: &str,
+	docs_url: &str,
+	replacement: &str,
+	opts: &Options,
+	diagnostics: &mut Diagnostics,
+) -> String {
+	let masked = markdown::mask_code(readme);
+	let mut output = String::with_capacity(readme.len());
+	let mut end = 0;
+	for region in protected_regions(readme, diagnostics) {
+		// Regions overlapping the ones before them (like a `<code>` element around a marker) are merged with them
+		let start = region.start.max(end);
+		if start > end {
+			output.push_str(&replace_in(
+				&readme[end..start],
+				&masked[end..start],
+				docs_url,
+				replacement,
+				opts,
+			));
+		}
+		if region.end > start {
+			output.push_str(&readme[start..region.end]);
+		}
+		end = end.max(region.end);
+	}
+	output.push_str(&replace_in(&readme[end..], &masked[end..], docs_url, replacement, opts));
+	output
+}
+
+/// Finds the byte ranges of the parts of the readme the docs URL shouldn't be replaced in, sorted by their start: the
+/// contents of no-replace regions (with markers outside of code) and HTML `<code>` elements.
+/// Anything after an opening marker without a matching closing marker counts, with a warning.
+fn protected_regions(readme: &str, diagnostics: &mut Diagnostics) -> Vec<Range<usize>> {
+	let masked = markdown::mask_code(readme);
+	let marker_re = RegexBuilder::new(r"<!--\s*(/?)docify:noreplace\s*-->")
+		.case_insensitive(true)
+		.build()
+		.expect("unable to build no-replace marker regex");
+	let code_re = RegexBuilder::new(r"<code\b[^>]*>.*?</code\s*>")
+		.case_insensitive(true)
+		.dot_matches_new_line(true)
+		.build()
+		.expect("unable to build code element regex");
+
+	let mut regions = Vec::new();
+	let mut region_start = None;
+	for marker in marker_re.captures_iter(&masked) {
+		let Some(whole) = marker.get(0) else {
+			continue;
+		};
+
+		// Ignore markers that don't change anything (a closing marker outside a region, or an opening one inside)
+		match (region_start, marker[1].is_empty()) {
+			(None, true) => region_start = Some(whole.end()),
+			(Some(start), false) => {
+				regions.push(start..whole.start());
+				region_start = None;
+			}
+			_ => {}
+		}
+	}
+	if let Some(start) = region_start {
+		diagnostics.warn(
+			"`docify:noreplace` marker has no matching `/docify:noreplace` marker, so the docs URL isn't replaced in \
+			 the rest of the readme",
+		);
+		regions.push(start..readme.len());
+	}
+
+	regions.extend(code_re.find_iter(readme).map(|code| code.range()));
+	regions.sort_by_key(|region| region.start);
+	regions
+}
+
+/// Replaces all occurrences of the docs URL with its replacement in a piece of the readme, given along with its
+/// [masked](markdown::mask_code) copy. Links are only looked for in prose, so occurrences in code are always just
+/// replaced, without titles or intra-doc paths that would change what the code means.
+fn replace_in(readme: &str, masked: &str, docs_url: &str, replacement: &str, opts: &Options) -> String {
+	if (!opts.keep_original_as_title && opts.relativize == Relativize::Url) || docs_url.is_empty() {
+		return readme.replace(docs_url, replacement);
+	}
+
+	// Match inline link destinations, reference definition destinations (which may be on the line after the label),
+	// HTML links and images, and bare occurrences of the URL, in that order of preference, so that titles are only
+	// added to actual link destinations
+	let title = r#"(?:"[^"]*"|'[^']*'|\([^)]*\))"#;
+	let inline = format!(r"(?P<inline>\]\()(?P<inline_dest>[^\s)]+)(?P<inline_title>\s+{title})?\)");
+	let label = r"^ {0,3}\[[^\]]+\]:[ \t]*(?:(?:\r\n|\r|\n)[ \t]*)?";
+	let def = format!(r"(?P<def>{label})(?P<def_dest>\S+)(?P<def_title>[ \t]+{title})?");
+	let tag = r"(?P<tag>(?i:<(?:a|img)\b[^>]*>))";
+	let re = Regex::new(&format!("(?m){inline}|{def}|{tag}|{}", regex::escape(docs_url)))
+		.expect("unable to build link regex");
+
+	let mut output = String::with_capacity(readme.len());
+	let mut end = 0;
+	for caps in re.captures_iter(masked) {
+		let Some(whole) = caps.get(0) else {
+			continue;
+		};
+		output.push_str(&readme[end..whole.start()].replace(docs_url, replacement));
+		output.push_str(&rewrite_link(readme, &caps, docs_url, replacement, opts));
+		end = whole.end();
+	}
+	output.push_str(&readme[end..].replace(docs_url, replacement));
+	output
+}
+
+/// Rewrites a match of the link regex in [`replace_in`], taking the matched text from the readme rather than the masked
+/// copy it was matched on, since link labels may have code in them
+fn rewrite_link(readme: &str, caps: &Captures<'_>, docs_url: &str, replacement: &str, opts: &Options) -> String {
+	let text = |name| caps.name(name).map(|m| &readme[m.range()]);
+	if let Some(tag) = text("tag") {
+		retitle_tag(tag, docs_url, replacement, opts)
+	} else if let (Some(prefix), Some(dest)) = (text("inline"), text("inline_dest")) {
+		let title = retitle(dest, text("inline_title"), docs_url, opts);
+		format!("{prefix}{}{title})", rewrite_dest(dest, docs_url, replacement, opts))
+	} else if let (Some(prefix), Some(dest)) = (text("def"), text("def_dest")) {
+		let title = retitle(dest, text("def_title"), docs_url, opts);
+		format!("{prefix}{}{title}", rewrite_dest(dest, docs_url, replacement, opts))
+	} else {
+		replacement.to_owned()
+	}
+}
+
+/// Replaces the docs URL in the `href` and `src` attributes of an HTML tag, adding a `title` attribute with the
+/// original URL if [`Options::keep_original_as_title`] is set and the tag doesn't already have one
+fn retitle_tag(tag: &str, docs_url: &str, replacement: &str, opts: &Options) -> String {
+	let attr_re = RegexBuilder::new(r#"(?P<name>\b(?:href|src)\s*=\s*)(?P<quote>["'])(?P<dest>.*?)["']"#)
+		.case_insensitive(true)
+		.build()
+		.expect("unable to build HTML attribute regex");
+	let title_re = RegexBuilder::new(r"\btitle\s*=")
+		.case_insensitive(true)
+		.build()
+		.expect("unable to build HTML title regex");
+
+	let mut title = (opts.keep_original_as_title && !title_re.is_match(tag)).then_some(());
+	attr_re
+		.replace_all(tag, |caps: &Captures<'_>| {
+			let (dest, quote) = (&caps["dest"], &caps["quote"]);
+			let attr = format!("{}{quote}{}{quote}", &caps["name"], dest.replace(docs_url, replacement));
+			match title.take_if(|()| dest.contains(docs_url)) {
+				Some(()) => format!(r#"{attr} title="{}""#, dest.replace('"', "&quot;")),
+				None => attr,
+			}
+		})
+		.into_owned()
+}
+
+/// Determines the title a link destination should have after replacement, keeping any existing title as-is and only
+/// adding one if [`Options::keep_original_as_title`] is set
+fn retitle(dest: &str, title: Option<&str>, docs_url: &str, opts: &Options) -> String {
+	match title {
+		Some(title) => title.to_owned(),
+		None if opts.keep_original_as_title && dest.contains(docs_url) => {
+			format!(r#" "{}""#, dest.replace('"', r#"\""#))
+		}
+		None => String::new(),
+	}
+}
+
+/// Rewrites a link destination, turning it into an intra-doc link path if [`Options::relativize`] asks for that and
+/// it points to an item page, or replacing the docs URL in it otherwise
+fn rewrite_dest(dest: &str, docs_url: &str, replacement: &str, opts: &Options) -> String {
+	match opts.relativize {
+		Relativize::IntraDoc => intra_doc_path(dest, docs_url).unwrap_or_else(|| dest.replace(docs_url, replacement)),
+		Relativize::Url => dest.replace(docs_url, replacement),
+	}
+}
+
+/// Derives the intra-doc link path (e.g. `crate::module::Thing::new`) of the item a destination starting with the docs
+/// URL points to, from the structure of rustdoc's URLs: module directories, followed by an item page
+/// (`struct.Thing.html`) or module index (`index.html`, or nothing), and optionally an anchor for one of the item's
+/// members (`#method.new`). Destinations that don't follow that structure have no path.
+fn intra_doc_path(dest: &str, docs_url: &str) -> Option<String> {
+	let is_ident = |segment: &str| {
+		segment.chars().all(|c| c.is_alphanumeric() || c == '_')
+			&& segment.chars().next().is_some_and(|c| !c.is_numeric())
+	};
+
+	let rest = dest.strip_prefix(docs_url)?;
+	let (page, member) = rest
+		.split_once('#')
+		.map_or((rest, None), |(page, member)| (page, Some(member)));
+	let (modules, file) = page.rsplit_once('/').unwrap_or(("", page));
+
+	let mut path = vec!["crate"];
+	for module in modules.split('/').filter(|module| !module.is_empty()) {
+		path.push(Some(module).filter(|module| is_ident(module))?);
+	}
+	if !matches!(file, "" | "index.html") {
+		let (kind, name) = file.strip_suffix(".html")?.split_once('.')?;
+		path.push(Some(name).filter(|name| ITEM_KINDS.contains(&kind) && is_ident(name))?);
+	}
+	if let Some(member) = member {
+		let (kind, name) = member.split_once('.')?;
+		path.push(Some(name).filter(|name| MEMBER_KINDS.contains(&kind) && is_ident(name))?);
+	}
+
+	Some(path.join("::"))
+}
+
+/// Sets the destination of every link outside of code whose text matches the given text (compared the same way
+/// reference labels are), regardless of its current destination. Inline links keep their titles, and reference links
+/// with a definition are turned into inline links.
+pub(crate) fn retarget_by_text(readme: &str, text: &str, dest: &str) -> String {
+	let masked = markdown::mask_code(readme);
+	let defined = reference_definitions(readme, &masked);
+	let text = normalize_label(text);
+	let inline_re =
+		Regex::new(r"(!?)\[([^\[\]\r\n]+)\]\([ \t]*(<[^>\r\n]*>|[^\s)]*)").expect("unable to build inline link regex");
+
+	let mut edits: Vec<(Range<usize>, String)> = inline_re
+		.captures_iter(&masked)
+		.filter_map(|caps| {
+			let (label, old_dest) = (caps.get(2)?, caps.get(3)?);
+			let is_match = caps[1].is_empty()
+				&& !masked[..caps.get(0)?.start()].ends_with('\\')
+				&& normalize_label(&readme[label.range()]) == text;
+			is_match.then(|| (old_dest.range(), dest.to_owned()))
+		})
+		.collect();
+
+	for reference in references(readme, &masked) {
+		if normalize_label(reference.text) == text && defined.contains(&normalize_label(reference.label)) {
+			edits.push((reference.range, format!("[{}]({dest})", reference.text)));
+		}
+	}
+	edits.sort_by_key(|(range, _)| range.start);
+
+	let mut output = String::with_capacity(readme.len());
+	let mut end = 0;
+	for (range, replacement) in &edits {
+		output.push_str(&readme[end..range.start]);
+		output.push_str(replacement);
+		end = range.end;
+	}
+	output.push_str(&readme[end..]);
+	output
+}
+
+/// Merges reference definitions outside of code that have the same destination (and title), keeping only the first of
+/// them and pointing the references to the others at it instead, so the definitions stay tidy after rewriting
+pub(crate) fn dedupe_references(readme: &str) -> String {
+	let masked = markdown::mask_code(readme);
+	let def_re = Regex::new(r"^ {0,3}\[([^\]]+)\]:[ \t]*(\S+)(.*)$").expect("unable to build definition regex");
+
+	// Find the definitions that duplicate an earlier one, mapping their labels to the earlier one's label
+	let mut first_labels = BTreeMap::new();
+	let mut duplicates = BTreeMap::new();
+	let mut edits: Vec<(Range<usize>, String)> = Vec::new();
+	for line in markdown::lines(&masked) {
+		let Some(caps) = def_re.captures(line.content) else {
+			continue;
+		};
+		let (Some(label), Some(dest), Some(title)) = (caps.get(1), caps.get(2), caps.get(3)) else {
+			continue;
+		};
+		let label = &readme[line.start.saturating_add(label.start())..line.start.saturating_add(label.end())];
+		let content = &readme[line.start..line.end()];
+		let key = (
+			content[dest.range()].to_owned(),
+			content[title.range()].trim().to_owned(),
+		);
+
+		match first_labels.get(&key) {
+			Some(&first) if normalize_label(first) != normalize_label(label) => {
+				duplicates.insert(normalize_label(label), first);
+				edits.push((line.start..line.next_start(), String::new()));
+			}
+			Some(_) => {}
+			None => {
+				first_labels.insert(key, label);
+			}
+		}
+	}
+
+	// Point the references to the removed definitions at the remaining ones
+	for reference in references(readme, &masked) {
+		let Some(first) = duplicates.get(&normalize_label(reference.label)) else {
+			continue;
+		};
+		edits.push((reference.range, format!("[{}][{first}]", reference.text)));
+	}
+	edits.sort_by_key(|(range, _)| range.start);
+
+	let mut output = String::with_capacity(readme.len());
+	let mut end = 0;
+	for (range, replacement) in &edits {
+		output.push_str(&readme[end..range.start]);
+		output.push_str(replacement);
+		end = range.end;
+	}
+	output.push_str(&readme[end..]);
+	output
+}
+
+/// Warns about every anchor link (`#anchor`) in the transformed readme that pointed to a heading in the original
+/// readme, but doesn't anymore since the heading has been removed
+pub(crate) fn check_anchors(original: &str, transformed: &str, diagnostics: &mut Diagnostics) {
+	let (before, after) = (heading_anchors(original), heading_anchors(transformed));
+	let masked = markdown::mask_code(transformed);
+	let re =
+		Regex::new(r"(?m)(?:\]\(|^ {0,3}\[[^\]]+\]:[ \t]*)<?#([^\s)>]+)").expect("unable to build anchor link regex");
+
+	let mut reported = BTreeSet::new();
+	for caps in re.captures_iter(&masked) {
+		let Some(anchor) = caps.get(1).map(|anchor| &transformed[anchor.range()]) else {
+			continue;
+		};
+		if before.contains(anchor) && !after.contains(anchor) && reported.insert(anchor) {
+			diagnostics.warn(format!(
+				"anchor link `#{anchor}` no longer resolves, since its heading has been removed"
+			));
+		}
+	}
+}
+
+/// Gets the anchors of all headings in the readme, generated the same way GitHub and rustdoc do (lowercased, with
+/// spaces turned into hyphens, other punctuation removed, and a numeric suffix for repeats)
+fn heading_anchors(readme: &str) -> BTreeSet<String> {
+	let mut anchors = BTreeSet::new();
+	for heading in markdown::headings(readme) {
+		let slug: String = heading
+			.text
+			.trim()
+			.to_lowercase()
+			.chars()
+			.filter_map(|c| match c {
+				' ' => Some('-'),
+				c if c.is_alphanumeric() || c == '-' || c == '_' => Some(c),
+				_ => None,
+			})
+			.collect();
+
+		let mut anchor = slug.clone();
+		let mut repeat = 0_usize;
+		while anchors.contains(&anchor) {
+			repeat = repeat.saturating_add(1);
+			anchor = format!("{slug}-{repeat}");
+		}
+		anchors.insert(anchor);
+	}
+	anchors
+}
+
+/// Warns about every reference link in the readme that has no matching definition and isn't one of the known items,
+/// since rustdoc would try (and fail) to resolve it as an intra-doc link
+pub(crate) fn check_references(readme: &str, known_items: &BTreeSet<String>, diagnostics: &mut Diagnostics) {
+	let masked = markdown::mask_code(readme);
+	let defined = reference_definitions(readme, &masked);
+
+	let mut reported = BTreeSet::new();
+	for reference in references(readme, &masked) {
+		let (label, item) = (reference.label, reference.item());
+		if !defined.contains(&normalize_label(label)) && !known_items.contains(item) && reported.insert(item) {
+			diagnostics.warn(format!(
+				"reference `[{label}]` has no definition and doesn't match any of the known items"
+			));
+		}
+	}
+}
+
+/// Escapes the brackets of every shortcut (`[label]`) or collapsed (`[label][]`) reference in the prose of the readme
+/// that has no matching definition and isn't one of the known items, so rustdoc renders them as literal text (like
+/// GitHub does) rather than trying to resolve them as intra-doc links
+pub(crate) fn escape_bare_brackets(readme: &str, known_items: Option<&BTreeSet<String>>) -> String {
+	let masked = markdown::mask_code(readme);
+	let defined = reference_definitions(readme, &masked);
+
+	let mut output = String::with_capacity(readme.len());
+	let mut end = 0;
+	for reference in references(readme, &masked) {
+		let known = known_items.is_some_and(|known| known.contains(reference.item()));
+		if reference.full || known || defined.contains(&normalize_label(reference.label)) {
+			continue;
+		}
+
+		output.push_str(&readme[end..reference.range.start]);
+		output.push_str(&readme[reference.range.clone()].replace('[', r"\[").replace(']', r"\]"));
+		end = reference.range.end;
+	}
+	output.push_str(&readme[end..]);
+	output
+}
+
+/// Finds the names of the items referred to by the readme's reference links (ignoring surrounding backticks)
+pub(crate) fn referenced_items(readme: &str) -> BTreeSet<&str> {
+	references(readme, &markdown::mask_code(readme))
+		.iter()
+		.map(Reference::item)
+		.collect()
+}
+
+/// Reference link found in a readme
+struct Reference<'a> {
+	/// Byte range of the whole reference (all of its brackets) in the readme
+	range: Range<usize>,
+
+	/// Label of the reference
+	label: &'a str,
+
+	/// Visible text of the reference (the same as the label, unless it's a full reference)
+	text: &'a str,
+
+	/// Whether this is a full reference (`[text][label]`) rather than a shortcut or collapsed one
+	full: bool,
+}
+
+impl<'a> Reference<'a> {
+	/// Gets the name of the item the label refers to if it were an intra-doc link, ignoring surrounding backticks
+	fn item(&self) -> &'a str {
+		self.label.trim_matches('`')
+	}
+}
+
+/// Finds the normalized labels of all reference definitions (`[label]: destination`) in the readme, searching its
+/// [masked](markdown::mask_code) copy so that nothing in code is matched
+fn reference_definitions(readme: &str, masked: &str) -> BTreeSet<String> {
+	let re = Regex::new(r"(?m)^ {0,3}\[([^\]]+)\]:").expect("unable to build definition regex");
+	re.captures_iter(masked)
+		.filter_map(|caps| caps.get(1))
+		.map(|label| normalize_label(&readme[label.range()]))
+		.collect()
+}
+
+/// Finds all shortcut (`[label]`), collapsed (`[label][]`), and full (`[text][label]`) reference links in the readme,
+/// searching its [masked](markdown::mask_code) copy so that nothing in code is matched
+fn references<'a>(readme: &'a str, masked: &str) -> Vec<Reference<'a>> {
+	let re = Regex::new(r"(!?)\[([^\[\]\r\n]+)\](?:\[([^\[\]\r\n]*)\])?").expect("unable to build reference regex");
+	let list_item_re = Regex::new(r"^\s*(?:[-*+]|\d+[.)])\s+$").expect("unable to build list item regex");
+
+	re.captures_iter(masked)
+		.filter_map(|caps| {
+			let whole = caps.get(0)?;
+			let (before, after) = (&masked[..whole.start()], &masked[whole.end()..]);
+			let line_start = &before[before.rfind('\n').map_or(0, |idx| idx.saturating_add(1))..];
+			let full_label = caps.get(3).filter(|label| !label.is_empty());
+
+			// Skip images, escaped brackets, inline links, definitions, footnotes, and task list checkboxes
+			let skip = !caps[1].is_empty()
+				|| before.ends_with('\\')
+				|| (full_label.is_none() && (after.starts_with('(') || after.starts_with(':')))
+				|| caps[2].starts_with('^')
+				|| (full_label.is_none() && list_item_re.is_match(line_start));
+			let text = caps.get(2)?;
+			let label = full_label.unwrap_or(text);
+			(!skip).then(|| Reference {
+				range: whole.range(),
+				label: &readme[label.range()],
+				text: &readme[text.range()],
+				full: full_label.is_some(),
+			})
+		})
+		.collect()
+}
+
+/// Normalizes a reference label for matching the same way markdown does: case-insensitively, with collapsed whitespace
+fn normalize_label(label: &str) -> String {
+	label.split_whitespace().collect::<Vec<_>>().join(" ").to_lowercase()
+}
+
+/// Turns link destinations (of inline links and reference definitions) outside of code that start with the relative
+/// docs URL back into absolute ones starting with the docs URL, reversing [`replace_docs_url`]
+pub(crate) fn absolutize(readme: &str, relative: &str, docs_url: &str) -> String {
+	if relative.is_empty() {
+		return readme.to_owned();
+	}
+
+	let masked = markdown::mask_code(readme);
+	let re = Regex::new(r"(?m)(?:\]\(|^ {0,3}\[[^\]]+\]:[ \t]*(?:(?:\r\n|\r|\n)[ \t]*)?)<?")
+		.expect("unable to build link destination regex");
+
+	let mut output = String::with_capacity(readme.len());
+	let mut end = 0;
+	for dest in re.find_iter(&masked) {
+		if !readme[dest.end()..].starts_with(relative) {
+			continue;
+		}
+
+		output.push_str(&readme[end..dest.end()]);
+		output.push_str(docs_url);
+		end = dest.end().saturating_add(relative.len());
+	}
+	output.push_str(&readme[end..]);
+	output
+}
+
+#[cfg(test)]
+mod tests {
+	//! Tests for the link transformations
+
+	use super::*;
+	use crate::{diagnostics::Level, document};
+
+	/// Docs URL used in the tests
+	const DOCS_URL: &str = "https://docs.rs/some_crate/";
+
+	/// Replaces [`DOCS_URL`] with `./` in a readme with the default options, along with the diagnostics recorded
+	fn replace(readme: &str) -> (String, Diagnostics) {
+		let mut diagnostics = Diagnostics::new(false);
+		let replaced = replace_docs_url(readme, DOCS_URL, "./", &Options::default(), &mut diagnostics);
+		(replaced, diagnostics)
+	}
+
+	/// Docs URL used in the fixtures
+	const LATEST_URL: &str = "https://docs.rs/some_crate/latest/some_crate/";
+
+	/// Replaces [`LATEST_URL`] with the given replacement in a readme with the given options
+	fn replace_latest(readme: &str, replacement: &str, opts: &Options) -> String {
+		replace_docs_url(readme, LATEST_URL, replacement, opts, &mut Diagnostics::new(false))
+	}
+
+	/// Replacing with an empty string makes links relative, without any warnings
+	#[test]
+	fn replace_docs_url_removes_url() {
+		let mut diagnostics = Diagnostics::new(false);
+		assert_eq!(
+			replace_docs_url(
+				include_str!("../tests/fixtures/links.md"),
+				LATEST_URL,
+				"",
+				&Options::default(),
+				&mut diagnostics
+			),
+			"See [`Thing`](struct.Thing.html) and [Other].\n\n[Other]: struct.Other.html\n"
+		);
+		assert!(diagnostics.messages().is_empty());
+	}
+
+	/// Rewritten link destinations keep the original URL as their title if asked, wherever the destination is
+	#[test]
+	fn replace_docs_url_keeps_original_as_title() {
+		let opts = Options {
+			keep_original_as_title: true,
+			..Options::default()
+		};
+		assert_eq!(
+			replace_latest(include_str!("../tests/fixtures/links.md"), "./", &opts),
+			"See [`Thing`](./struct.Thing.html \"https://docs.rs/some_crate/latest/some_crate/struct.Thing.html\") and \
+			 [Other].\n\n\
+			 [Other]: ./struct.Other.html \"https://docs.rs/some_crate/latest/some_crate/struct.Other.html\"\n"
+		);
+		assert!(replace_latest(include_str!("../tests/fixtures/wrapped_definition.md"), "./", &opts).ends_with(
+			"[`Thing`]:\n    ./struct.Thing.html \"https://docs.rs/some_crate/latest/some_crate/struct.Thing.html\"\n"
+		));
+	}
+
+	/// Links in code don't get a title, but their URL is still replaced like everywhere else in the readme
+	#[test]
+	fn replace_docs_url_keeps_titles_out_of_code() {
+		let opts = Options {
+			keep_original_as_title: true,
+			..Options::default()
+		};
+		assert_eq!(
+			replace_latest(include_str!("../tests/fixtures/code_links.md"), "./", &opts),
+			"See [`Thing`](./struct.Thing.html \"https://docs.rs/some_crate/latest/some_crate/struct.Thing.html\").\n\n\
+			 ```rust\nlet link = \"[Thing](./struct.Thing.html)\";\n```\n"
+		);
+	}
+
+	/// Links to items become intra-doc paths, while other occurrences of the docs URL are replaced as usual
+	#[test]
+	fn replace_docs_url_makes_intra_doc_paths() {
+		let opts = Options {
+			relativize: Relativize::IntraDoc,
+			..Options::default()
+		};
+		assert_eq!(
+			replace_latest(include_str!("../tests/fixtures/intra_doc.md"), "./", &opts),
+			"See [`Thing`](crate::Thing), [`Thing::new`](crate::Thing::new), and [`Nested`](crate::sub::inner::Nested).\
+			 \n\n\
+			 The [sub] module is listed with [all items](./all.html).\n\n[sub]: crate::sub\n"
+		);
+		assert_eq!(
+			replace_latest(include_str!("../tests/fixtures/wrapped_definition.md"), "./", &opts),
+			"See [`Thing`].\n\n[`Thing`]:\n    crate::Thing\n"
+		);
+	}
+
+	/// Links in code never become intra-doc paths, which would change what the code means
+	#[test]
+	fn replace_docs_url_keeps_intra_doc_paths_out_of_code() {
+		let opts = Options {
+			relativize: Relativize::IntraDoc,
+			..Options::default()
+		};
+		assert_eq!(
+			replace_latest(include_str!("../tests/fixtures/code_links.md"), "./", &opts),
+			"See [`Thing`](crate::Thing).\n\n```rust\nlet link = \"[Thing](./struct.Thing.html)\";\n```\n"
+		);
+	}
+
+	/// HTML `<code>` is left alone, while HTML links are rewritten
+	#[test]
+	fn replace_docs_url_skips_html_code() {
+		assert_eq!(
+			replace_latest(include_str!("../tests/fixtures/html.md"), "./", &Options::default()),
+			"<a href=\"./struct.Thing.html\">Thing</a> lives under\n\
+			 <code>https://docs.rs/some_crate/latest/some_crate/</code>.\n"
+		);
+	}
+
+	/// Links with the given text are pointed at the destination, turning reference links into inline ones
+	#[test]
+	fn retarget_by_text_matches_case_insensitively() {
+		assert_eq!(
+			retarget_by_text(include_str!("../tests/fixtures/by_text.md"), "docs", "./index.html"),
+			"Read the [docs](./index.html \"Documentation\") or the [Docs](./index.html), \
+			 but not [the guide](./guide.html).\n\n\
+			 [Docs]: https://example.com/docs\n"
+		);
+	}
+
+	/// Definitions that end up the same are merged into the first of them
+	#[test]
+	fn dedupe_references_merges_definitions() {
+		let readme = replace_latest(
+			include_str!("../tests/fixtures/duplicates.md"),
+			"./",
+			&Options::default(),
+		);
+		assert_eq!(
+			dedupe_references(&readme),
+			"See [Thing], [the thing type][Thing], and [`Thing`][Thing].\n\n[Thing]: ./struct.Thing.html\n"
+		);
+	}
+
+	/// Brackets that don't form a link are escaped, leaving links and known items alone
+	#[test]
+	fn escape_bare_brackets_leaves_links() {
+		let readme = include_str!("../tests/fixtures/brackets.md");
+		assert_eq!(
+			escape_bare_brackets(readme, None),
+			"Values are \\[optional\\], see [the docs] or [`Thing`](https://docs.rs/some_crate/struct.Thing.html).\n\n\
+			 [the docs]: https://docs.rs/some_crate/index.html\n"
+		);
+		assert_eq!(
+			escape_bare_brackets(readme, Some(&BTreeSet::from(["optional".to_owned()]))),
+			readme
+		);
+	}
+
+	/// References without a definition or a matching known item are warned about, outside of code and task lists
+	#[test]
+	fn check_references_warns_about_unknown_items() {
+		let readme = include_str!("../tests/fixtures/references.md");
+		let mut diagnostics = Diagnostics::new(false);
+		check_references(readme, &BTreeSet::from(["Thing".to_owned()]), &mut diagnostics);
+		assert!(matches!(diagnostics.messages(), [(Level::Warning, message)] if message.contains("[Missing]")));
+
+		let mut diagnostics = Diagnostics::new(false);
+		check_references(
+			readme,
+			&BTreeSet::from(["Thing".to_owned(), "Missing".to_owned()]),
+			&mut diagnostics,
+		);
+		assert!(diagnostics.messages().is_empty());
+	}
+
+	/// Anchor links to headings that were removed are warned about
+	#[test]
+	fn check_anchors_warns_about_removed_headings() {
+		let readme = include_str!("../tests/fixtures/anchors.md");
+		let mut diagnostics = Diagnostics::new(false);
+		check_anchors(readme, readme, &mut diagnostics);
+		assert!(diagnostics.messages().is_empty());
+
+		check_anchors(readme, &document::strip_title(readme), &mut diagnostics);
+		assert!(matches!(diagnostics.messages(), [(Level::Warning, message)] if message.contains("#some-crate")));
+	}
+
+	/// Destinations starting with the relative URL are turned back into absolute ones
+	#[test]
+	fn absolutize_restores_docs_url() {
+		assert_eq!(
+			absolutize(include_str!("../tests/fixtures/target.md"), "./", LATEST_URL),
+			"See [`Thing`](https://docs.rs/some_crate/latest/some_crate/struct.Thing.html) and [the docs].\n\n\
+			 ```rust\n# use some_crate::Thing;\nlet thing = Thing::new()?;\n```\n\n\
+			 [the docs]: https://docs.rs/some_crate/latest/some_crate/index.html\n"
+		);
+	}
+
+	/// The docs URL isn't replaced between no-replace markers
+	#[test]
+	fn replace_docs_url_skips_noreplace_regions() {
+		let (replaced, diagnostics) = replace(
+			"[a](https://docs.rs/some_crate/a)\n<!-- docify:noreplace -->\n[b](https://docs.rs/some_crate/b)\n\
+			 <!-- /docify:noreplace -->\n[c](https://docs.rs/some_crate/c)\n",
+		);
+		assert_eq!(
+			replaced,
+			"[a](./a)\n<!-- docify:noreplace -->\n[b](https://docs.rs/some_crate/b)\n<!-- /docify:noreplace -->\n\
+			 [c](./c)\n"
+		);
+		assert!(diagnostics.messages().is_empty());
+	}
+
+	/// Overlapping regions are merged, so a `<code>` element that outlasts a no-replace region is still left alone
+	#[test]
+	fn replace_docs_url_merges_overlapping_regions() {
+		let (replaced, _) = replace(
+			"<!-- docify:noreplace -->\n<code>https://docs.rs/some_crate/a\n<!-- /docify:noreplace -->\n\
+			 https://docs.rs/some_crate/b</code>\nhttps://docs.rs/some_crate/c\n",
+		);
+		assert_eq!(
+			replaced,
+			"<!-- docify:noreplace -->\n<code>https://docs.rs/some_crate/a\n<!-- /docify:noreplace -->\n\
+			 https://docs.rs/some_crate/b</code>\n./c\n"
+		);
+	}
+
+	/// A no-replace region keeps the absolute links of a changelog
+	#[test]
+	fn replace_docs_url_keeps_changelog_links() {
+		let mut diagnostics = Diagnostics::new(false);
+		assert_eq!(
+			replace_docs_url(
+				include_str!("../tests/fixtures/noreplace.md"),
+				"https://docs.rs/some_crate/1.0.0/",
+				"./",
+				&Options::default(),
+				&mut diagnostics
+			),
+			"See [`Thing`](./struct.Thing.html).\n\n<!-- docify:noreplace -->\n\
+			 - v1.0.0: added [`Thing`](https://docs.rs/some_crate/1.0.0/struct.Thing.html)\n\
+			 <!-- /docify:noreplace -->\n"
+		);
+	}
+
+	/// Markers in code don't start a no-replace region
+	#[test]
+	fn replace_docs_url_ignores_markers_in_code() {
+		let (replaced, diagnostics) = replace(
+			"Use `<!-- docify:noreplace -->` to keep links.\n\n```html\n<!-- docify:noreplace -->\n```\n\n\
+			 [Thing](https://docs.rs/some_crate/struct.Thing.html)\n",
+		);
+		assert_eq!(
+			replaced,
+			"Use `<!-- docify:noreplace -->` to keep links.\n\n```html\n<!-- docify:noreplace -->\n```\n\n\
+			 [Thing](./struct.Thing.html)\n"
+		);
+		assert!(diagnostics.messages().is_empty());
+	}
+
+	/// HTML links and images only get a title with the original URL if that's asked for, even with intra-doc paths
+	#[test]
+	fn replace_docs_url_only_titles_tags_if_asked() {
+		let readme = "<a href=\"https://docs.rs/some_crate/struct.Thing.html\">Thing</a>\n";
+		let mut opts = Options {
+			relativize: Relativize::IntraDoc,
+			..Options::default()
+		};
+		let mut diagnostics = Diagnostics::new(false);
+		assert_eq!(
+			replace_docs_url(readme, DOCS_URL, "./", &opts, &mut diagnostics),
+			"<a href=\"./struct.Thing.html\">Thing</a>\n"
+		);
+
+		opts.keep_original_as_title = true;
+		assert_eq!(
+			replace_docs_url(readme, DOCS_URL, "./", &opts, &mut diagnostics),
+			"<a href=\"./struct.Thing.html\" title=\"https://docs.rs/some_crate/struct.Thing.html\">Thing</a>\n"
+		);
+	}
+
+	/// An unclosed marker protects the rest of the readme, with a warning
+	#[test]
+	fn replace_docs_url_warns_about_unclosed_marker() {
+		let (replaced, diagnostics) = replace(
+			"[a](https://docs.rs/some_crate/a)\n<!-- docify:noreplace -->\n[b](https://docs.rs/some_crate/b)\n",
+		);
+		assert_eq!(
+			replaced,
+			"[a](./a)\n<!-- docify:noreplace -->\n[b](https://docs.rs/some_crate/b)\n"
+		);
+		assert!(matches!(diagnostics.messages(), [(Level::Warning, message)] if message.contains("no matching")));
+	}
+}
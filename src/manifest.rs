@@ -0,0 +1,230 @@
+//! Reading of values from the crate's manifest (Cargo.toml)
+
+use std::{ffi::OsString, fs, path::Path};
+
+use syn::LitStr;
+
+/// Placeholders that can be used in the docs URLs, along with the manifest fields they're sourced from
+const PLACEHOLDERS: [(&str, &str); 2] = [("{{homepage}}", "homepage"), ("{{documentation}}", "documentation")];
+
+/// Reads the contents of the crate's manifest, if it can be found, looking up `CARGO_MANIFEST_DIR` with `var`
+pub(crate) fn read(var: impl Fn(&str) -> Option<OsString>) -> Option<String> {
+	let project_root = var("CARGO_MANIFEST_DIR")?;
+	fs::read_to_string(Path::new(&project_root).join("Cargo.toml")).ok()
+}
+
+/// Gets all string-valued `key = "value"` entries of a table in a manifest (e.g. `package`)
+pub(crate) fn table_entries(manifest: &str, table: &str) -> Vec<(String, String)> {
+	table_lines(manifest, table)
+		.filter_map(|(key, value)| Some((parse_key(key)?, parse_string(value.trim())?)))
+		.collect()
+}
+
+/// Gets the keys of all entries of a table in a manifest (e.g. `features`), whatever their values are
+pub(crate) fn table_keys(manifest: &str, table: &str) -> Vec<String> {
+	table_lines(manifest, table)
+		.filter_map(|(key, _)| parse_key(key))
+		.collect()
+}
+
+/// Splits the `key = value` lines of a table in a manifest into their unparsed keys and values
+fn table_lines<'a>(manifest: &'a str, table: &'a str) -> impl Iterator<Item = (&'a str, &'a str)> {
+	let mut in_table = false;
+	manifest.lines().map(str::trim).filter_map(move |line| {
+		if let Some(header) = line.strip_prefix('[') {
+			in_table = header.split(']').next().is_some_and(|name| name.trim() == table);
+			return None;
+		}
+		line.split_once('=').filter(|_| in_table)
+	})
+}
+
+/// Manifest table holding additional pairs of docs URLs and their replacements, as `"<docs_url>" = "<replacement>"`
+const REPLACEMENTS_TABLE: &str = "package.metadata.docify.replacements";
+
+/// Gets the pairs of docs URLs and their replacements from the manifest's [`REPLACEMENTS_TABLE`], with any
+/// placeholders in them expanded. Errors are spanned to the given readme path, since the pairs don't come from the
+/// macro's input. Environment variables are looked up with `var`.
+pub(crate) fn replacements(
+	path: &LitStr,
+	var: impl Fn(&str) -> Option<OsString>,
+) -> syn::Result<Vec<(String, String)>> {
+	read(&var).map_or_else(|| Ok(Vec::new()), |manifest| replacements_in(&manifest, path, &var))
+}
+
+/// Gets the pairs of docs URLs and their replacements from the [`REPLACEMENTS_TABLE`] of the given manifest, as in
+/// [`replacements`]
+fn replacements_in(
+	manifest: &str,
+	path: &LitStr,
+	var: impl Fn(&str) -> Option<OsString>,
+) -> syn::Result<Vec<(String, String)>> {
+	table_entries(manifest, REPLACEMENTS_TABLE)
+		.into_iter()
+		.map(|(docs_url, replacement)| {
+			Ok((
+				expand_placeholders(&LitStr::new(&docs_url, path.span()), &var)?,
+				expand_placeholders(&LitStr::new(&replacement, path.span()), &var)?,
+			))
+		})
+		.collect()
+}
+
+/// Expands the `{{homepage}}` and `{{documentation}}` placeholders in a URL given to the macro, using the values of
+/// the corresponding manifest fields. Environment variables are looked up with `var`.
+pub(crate) fn expand_placeholders(url: &LitStr, var: impl Fn(&str) -> Option<OsString>) -> syn::Result<String> {
+	let mut value = url.value();
+
+	for (placeholder, field) in PLACEHOLDERS {
+		if !value.contains(placeholder) {
+			continue;
+		}
+
+		let replacement = match field {
+			// Cargo exposes the homepage directly, but not the documentation URL
+			"homepage" => var("CARGO_PKG_HOMEPAGE").and_then(|homepage| homepage.into_string().ok()),
+			_ => read(&var).and_then(|manifest| {
+				table_entries(&manifest, "package")
+					.into_iter()
+					.find_map(|(key, value)| (key == field).then_some(value))
+			}),
+		}
+		.filter(|replacement| !replacement.is_empty())
+		.ok_or_else(|| {
+			syn::Error::new_spanned(
+				url,
+				format!("`{placeholder}` was used, but the crate's manifest has no `{field}` field set"),
+			)
+		})?;
+
+		value = value.replace(placeholder, &replacement);
+	}
+
+	Ok(value)
+}
+
+/// Parses a TOML key, either quoted or bare
+fn parse_key(key: &str) -> Option<String> {
+	parse_string(key.trim()).or_else(|| bare_key(key))
+}
+
+/// Parses a bare TOML key
+fn bare_key(key: &str) -> Option<String> {
+	let key = key.trim();
+	key.chars()
+		.all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+		.then(|| key.to_owned())
+		.filter(|key| !key.is_empty())
+}
+
+/// Parses a single-line TOML string (basic or literal), ignoring anything following it (such as a comment)
+fn parse_string(text: &str) -> Option<String> {
+	if let Some(rest) = text.strip_prefix('\'') {
+		return rest.split_once('\'').map(|(value, _)| value.to_owned());
+	}
+
+	let mut chars = text.strip_prefix('"')?.chars();
+	let mut value = String::new();
+	while let Some(c) = chars.next() {
+		match c {
+			'"' => return Some(value),
+			'\\' => match chars.next()? {
+				'n' => value.push('\n'),
+				't' => value.push('\t'),
+				other => value.push(other),
+			},
+			_ => value.push(c),
+		}
+	}
+
+	None
+}
+
+#[cfg(test)]
+mod tests {
+	//! Tests for reading values from manifests
+
+	use std::{env, process};
+
+	use proc_macro2::Span;
+
+	use super::*;
+	use crate::test_util::vars;
+
+	/// Homepage used for the placeholders in the tests
+	const HOMEPAGE: &str = "https://github.com/Gawdl3y/some_crate";
+
+	/// The replacements table is read in order, with placeholders expanded
+	#[test]
+	fn replacements_in_reads_table() {
+		let manifest = fs::read_to_string("tests/fixtures/metadata.toml").expect("unable to read fixture manifest");
+		let path = LitStr::new("README.md", Span::call_site());
+		assert_eq!(
+			replacements_in(&manifest, &path, vars(&[("CARGO_PKG_HOMEPAGE", HOMEPAGE)]))
+				.expect("replacements should be valid"),
+			[
+				(
+					"https://docs.rs/metadata_crate/latest/metadata_crate/".to_owned(),
+					"./".to_owned()
+				),
+				(
+					"https://github.com/Gawdl3y/metadata_crate/blob/main/".to_owned(),
+					format!("{HOMEPAGE}/blob/main/")
+				),
+			]
+		);
+	}
+
+	/// Placeholders are replaced with the homepage from the environment and the documentation URL from the manifest,
+	/// and using one that isn't set is an error
+	#[test]
+	fn expand_placeholders_uses_manifest_fields() {
+		let manifest_dir = env::temp_dir().join(format!("docify-manifest-{}", process::id()));
+		fs::create_dir_all(&manifest_dir).unwrap();
+		fs::write(
+			manifest_dir.join("Cargo.toml"),
+			"[package]\nname = \"some_crate\"\ndocumentation = \"https://docs.rs/some_crate\"\n",
+		)
+		.unwrap();
+		let env = [
+			("CARGO_PKG_HOMEPAGE", HOMEPAGE),
+			("CARGO_MANIFEST_DIR", manifest_dir.to_str().unwrap()),
+		];
+		let expand = |url, env| expand_placeholders(&LitStr::new(url, Span::call_site()), vars(env));
+
+		assert_eq!(
+			expand("{{homepage}}/blob/main/", &env).unwrap(),
+			format!("{HOMEPAGE}/blob/main/")
+		);
+		assert_eq!(
+			expand("{{documentation}}/latest/", &env).unwrap(),
+			"https://docs.rs/some_crate/latest/"
+		);
+		assert_eq!(
+			expand("https://docs.rs/some_crate/", &[]).unwrap(),
+			"https://docs.rs/some_crate/"
+		);
+
+		let err = expand("{{homepage}}/blob/main/", &env[1..]).unwrap_err();
+		assert_eq!(
+			err.to_string(),
+			"`{{homepage}}` was used, but the crate's manifest has no `homepage` field set"
+		);
+		let err = expand("{{documentation}}/latest/", &env[..1]).unwrap_err();
+		assert_eq!(
+			err.to_string(),
+			"`{{documentation}}` was used, but the crate's manifest has no `documentation` field set"
+		);
+		fs::remove_dir_all(&manifest_dir).unwrap();
+	}
+
+	/// Manifests without the table have no replacements
+	#[test]
+	fn replacements_in_allows_missing_table() {
+		let path = LitStr::new("README.md", Span::call_site());
+		let manifest = "[package]\nname = \"some_crate\"\n";
+		assert!(replacements_in(manifest, &path, vars(&[]))
+			.expect("replacements should be valid")
+			.is_empty());
+	}
+}
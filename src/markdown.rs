@@ -0,0 +1,332 @@
+//! Helpers for inspecting the markdown structure of a readme
+
+use std::{iter, ops::Range};
+
+use regex::Regex;
+
+/// Heading found in a readme
+pub(crate) struct Heading<'a> {
+	/// Byte range of the whole heading in the readme (including the underline of setext headings), excluding the
+	/// final line break
+	pub(crate) range: Range<usize>,
+
+	/// Level of the heading, from 1 to 6
+	pub(crate) level: usize,
+
+	/// Text of the heading
+	pub(crate) text: &'a str,
+}
+
+/// Finds all ATX (`#`-prefixed) and setext (`===`- or `---`-underlined) headings in the readme, outside of code blocks.
+/// A `---` line is only treated as an underline if it directly follows a line of paragraph text, since it's a thematic
+/// break otherwise.
+pub(crate) fn headings(readme: &str) -> Vec<Heading<'_>> {
+	let masked = mask_code(readme);
+	let atx_re =
+		Regex::new(r"^ {0,3}(#{1,6})(?:[ \t]+(.*?))??(?:[ \t]+#+)?[ \t]*$").expect("unable to build heading regex");
+	let underline_re = Regex::new(r"^ {0,3}(=+|-+)[ \t]*$").expect("unable to build setext underline regex");
+	let non_paragraph_re = Regex::new(
+		r"^(?: {4}|\t|\s*$|\s*(?:[-*+]|\d+[.)])(?:\s|$)|\s*(?:[>#<|]|```|~~~)|\s*(?:(?:\*\s*){3,}|(?:_\s*){3,})$)",
+	)
+	.expect("unable to build paragraph regex");
+
+	let mut headings = Vec::new();
+	let mut prev: Option<(Line<'_>, bool)> = None;
+	for line in lines(&masked) {
+		let mut is_paragraph = false;
+
+		if let Some(caps) = atx_re.captures(line.content) {
+			headings.push(Heading {
+				range: line.start..line.end(),
+				level: caps.get(1).map_or(1, |hashes| hashes.len()),
+				text: caps.get(2).map_or("", |text| {
+					&readme[line.start.saturating_add(text.start())..line.start.saturating_add(text.end())]
+				}),
+			});
+		} else if let (Some(caps), Some((text_line, true))) = (underline_re.captures(line.content), &prev) {
+			headings.push(Heading {
+				range: text_line.start..line.end(),
+				level: if caps[1].starts_with('=') { 1 } else { 2 },
+				text: readme[text_line.start..text_line.end()].trim(),
+			});
+		} else {
+			is_paragraph = !non_paragraph_re.is_match(line.content);
+		}
+
+		prev = Some((line, is_paragraph));
+	}
+	headings
+}
+
+/// Fenced code block found in a readme
+pub(crate) struct FencedBlock<'a> {
+	/// Byte range of the whole block in the readme, from the start of the opening fence to the end of the closing one
+	/// (or the end of the readme, if it's never closed)
+	pub(crate) range: Range<usize>,
+
+	/// Text preceding the opening fence on its line (indentation and/or blockquote markers)
+	pub(crate) indent: &'a str,
+
+	/// Opening fence (a run of at least three backticks or tildes)
+	pub(crate) fence: &'a str,
+
+	/// Info string following the opening fence
+	pub(crate) info: &'a str,
+
+	/// Line break following the opening fence
+	pub(crate) open_newline: &'a str,
+
+	/// Byte range of the block's contents in the readme
+	pub(crate) body_range: Range<usize>,
+
+	/// Contents of the block, excluding the line break preceding the closing fence
+	pub(crate) body: &'a str,
+
+	/// Line break preceding the closing fence (empty if the block has no contents)
+	pub(crate) newline: &'a str,
+
+	/// Closing fence, including any indentation preceding it on its line, if the block is closed
+	pub(crate) close: Option<&'a str>,
+}
+
+impl<'a> FencedBlock<'a> {
+	/// Splits the info string into its language (the first token) and attributes, which may be separated by commas or
+	/// whitespace. An opening brace also starts a new token, so highlighting hints like `rust{1,3}` don't affect the
+	/// language.
+	pub(crate) fn info_tokens(&self) -> impl Iterator<Item = &'a str> {
+		self.info.split([',', ' ', '\t', '{']).filter(|token| !token.is_empty())
+	}
+}
+
+/// Line of text, along with its position and the line break that follows it
+pub(crate) struct Line<'a> {
+	/// Byte offset of the start of the line
+	pub(crate) start: usize,
+
+	/// Contents of the line, excluding the line break
+	pub(crate) content: &'a str,
+
+	/// Line break following the line (empty for the last line if the text doesn't end with one)
+	pub(crate) newline: &'a str,
+}
+
+impl Line<'_> {
+	/// Gets the byte offset of the end of the line's contents (the start of the line break)
+	pub(crate) const fn end(&self) -> usize {
+		self.start.saturating_add(self.content.len())
+	}
+
+	/// Gets the byte offset of the start of the next line
+	pub(crate) const fn next_start(&self) -> usize {
+		self.end().saturating_add(self.newline.len())
+	}
+}
+
+/// Splits text into lines, recognizing `\r\n`, `\r`, and `\n` line breaks
+pub(crate) fn lines(text: &str) -> impl Iterator<Item = Line<'_>> {
+	let mut rest = text;
+	let mut start = 0;
+
+	iter::from_fn(move || {
+		if rest.is_empty() {
+			return None;
+		}
+
+		let (content, newline) = match rest.find(['\r', '\n']) {
+			Some(idx) => {
+				let (content, after) = rest.split_at(idx);
+				let newline = if after.starts_with("\r\n") {
+					&after[..2]
+				} else {
+					&after[..1]
+				};
+				(content, newline)
+			}
+			None => (rest, ""),
+		};
+
+		let line = Line {
+			start,
+			content,
+			newline,
+		};
+		rest = &text[line.next_start()..];
+		start = line.next_start();
+		Some(line)
+	})
+}
+
+/// Finds all fenced code blocks in the readme with a single pass over its lines.
+/// Fences may be preceded by indentation and blockquote markers, and a block is only closed by a fence of the same
+/// character that's at least as long as the opening one and has no info string, so nested fences are handled properly.
+pub(crate) fn fenced_blocks(readme: &str) -> Vec<FencedBlock<'_>> {
+	let mut blocks = Vec::new();
+	let mut open: Option<FencedBlock<'_>> = None;
+	let mut prev: Option<Line<'_>> = None;
+
+	for line in lines(readme) {
+		let rest = line.content.trim_start_matches([' ', '\t', '>']);
+		let indent = line.content.strip_suffix(rest).unwrap_or_default();
+		let marker = rest.chars().next().filter(|c| *c == '`' || *c == '~');
+		let after = marker.map_or(rest, |marker| rest.trim_start_matches(marker));
+		let fence = rest.strip_suffix(after).unwrap_or_default();
+
+		match &mut open {
+			// Closing fence for the current block
+			Some(block)
+				if !fence.is_empty()
+					&& block.fence.starts_with(fence.get(..1).unwrap_or_default())
+					&& fence.len() >= block.fence.len()
+					&& after.trim().is_empty() =>
+			{
+				let close_len = indent.len().saturating_add(fence.len());
+				let body_start = block.body_range.start;
+				let (body_end, newline) = match &prev {
+					Some(prev) if prev.next_start() > body_start => (prev.end(), prev.newline),
+					_ => (body_start, ""),
+				};
+
+				block.range.end = line.start.saturating_add(close_len);
+				block.body_range = body_start..body_end;
+				block.body = &readme[body_start..body_end];
+				block.newline = newline;
+				block.close = Some(&line.content[..close_len]);
+				blocks.extend(open.take());
+			}
+
+			// Opening fence for a new block
+			None if fence.len() >= 3 && !(fence.starts_with('`') && after.contains('`')) => {
+				let start = line.start.saturating_add(indent.len());
+				open = Some(FencedBlock {
+					range: start..readme.len(),
+					indent,
+					fence,
+					info: after,
+					open_newline: line.newline,
+					body_range: line.next_start()..readme.len(),
+					body: &readme[line.next_start()..],
+					newline: "",
+					close: None,
+				});
+			}
+
+			// Line inside the current block, or prose
+			_ => {}
+		}
+
+		prev = Some(line);
+	}
+
+	blocks.extend(open);
+	blocks
+}
+
+/// Gets a copy of the text with the contents of all fenced code blocks and inline code spans replaced with spaces
+/// (keeping the same length and line structure), so that prose can be searched without matching anything in code
+pub(crate) fn mask_code(text: &str) -> String {
+	let mut masked = String::with_capacity(text.len());
+	let mut end = 0;
+
+	for block in fenced_blocks(text) {
+		for line in text[end..block.range.start].split_inclusive('\n') {
+			masked.push_str(&mask_inline_code(line));
+		}
+		masked.push_str(&text[block.range.start..block.body_range.start]);
+		masked.push_str(&blank(block.body));
+		masked.push_str(&text[block.body_range.end..block.range.end]);
+		end = block.range.end;
+	}
+
+	for line in text[end..].split_inclusive('\n') {
+		masked.push_str(&mask_inline_code(line));
+	}
+	masked
+}
+
+/// Replaces the contents of inline code spans in a line with spaces
+fn mask_inline_code(line: &str) -> String {
+	let mut masked = String::with_capacity(line.len());
+	let mut rest = line;
+
+	while let Some(start) = rest.find('`') {
+		let (before, from_ticks) = rest.split_at(start);
+		let after = from_ticks.trim_start_matches('`');
+		let delim = from_ticks.strip_suffix(after).unwrap_or_default();
+		let Some((code, rest_after)) = after.split_once(delim) else {
+			break;
+		};
+
+		masked.push_str(before);
+		masked.push_str(delim);
+		masked.push_str(&blank(code));
+		masked.push_str(delim);
+		rest = rest_after;
+	}
+
+	masked.push_str(rest);
+	masked
+}
+
+/// Replaces every character of a string other than line breaks with a space, keeping the byte length the same
+fn blank(text: &str) -> String {
+	text.chars()
+		.map(|c| match c {
+			'\r' | '\n' => c.to_string(),
+			_ => " ".repeat(c.len_utf8()),
+		})
+		.collect()
+}
+
+#[cfg(test)]
+mod tests {
+	//! Tests for inspecting the markdown structure
+
+	use super::*;
+
+	/// Tilde fences work like backtick ones, and a block is only closed by a fence of the same character that's at
+	/// least as long as the opening one
+	#[test]
+	fn fenced_blocks_matches_closing_fences() {
+		let readme = "~~~python\nx = 1\n```\n~~~\n\n````rust\n```\ninner\n```\n`````\n";
+		let blocks = fenced_blocks(readme);
+		assert_eq!(blocks.len(), 2);
+		assert_eq!((blocks[0].fence, blocks[0].info), ("~~~", "python"));
+		assert_eq!((blocks[0].body, blocks[0].close), ("x = 1\n```", Some("~~~")));
+		assert_eq!((blocks[1].fence, blocks[1].info), ("````", "rust"));
+		assert_eq!((blocks[1].body, blocks[1].close), ("```\ninner\n```", Some("`````")));
+		assert_eq!(&readme[blocks[1].range.clone()], "````rust\n```\ninner\n```\n`````");
+	}
+
+	/// A block that's never closed runs to the end of the readme
+	#[test]
+	fn fenced_blocks_leaves_unclosed_block_open() {
+		let readme = "Intro.\n\n```rust\nlet x = 1;\n~~~\n";
+		let blocks = fenced_blocks(readme);
+		assert_eq!(blocks.len(), 1);
+		assert_eq!(blocks[0].range, 8..readme.len());
+		assert_eq!((blocks[0].body, blocks[0].close), ("let x = 1;\n~~~\n", None));
+	}
+
+	/// ATX and setext headings are found outside of code, while a `---` line after a list item is a thematic break
+	#[test]
+	fn headings_finds_atx_and_setext() {
+		let readme = "# Title #\n\nSetext\n======\n\nText\n---\n\n- item\n---\n\n```md\n# not a heading\n```\n";
+		let headings = headings(readme)
+			.into_iter()
+			.map(|heading| (heading.level, heading.text))
+			.collect::<Vec<_>>();
+		assert_eq!(headings, [(1, "Title"), (1, "Setext"), (2, "Text")]);
+	}
+
+	/// Code spans and block contents are blanked out, keeping the delimiters, fences and line breaks
+	#[test]
+	fn mask_code_blanks_code() {
+		let text = "Use `code` and ``a`b``.\n\n```rust\nlet x = 1;\r\nlet y = 2;\n```\nAfter `x`\n";
+		let masked = mask_code(text);
+		assert_eq!(
+			masked,
+			"Use `    ` and ``   ``.\n\n```rust\n          \r\n          \n```\nAfter ` `\n"
+		);
+		assert_eq!(masked.len(), text.len());
+	}
+}
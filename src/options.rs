@@ -0,0 +1,829 @@
+//! Parsing of the macro input and the options it accepts
+
+use std::{
+	collections::{BTreeMap, BTreeSet, HashSet},
+	env,
+	ffi::OsString,
+};
+
+use proc_macro2::{Delimiter, Group, Span, TokenStream};
+use quote::{quote, ToTokens, TokenStreamExt};
+use syn::{
+	bracketed, parenthesized,
+	parse::{Parse, ParseStream, Parser},
+	punctuated::Punctuated,
+	token, Ident, Lit, LitStr, Token,
+};
+
+use crate::{
+	codeblocks::{self, BODY_PLACEHOLDER},
+	report,
+};
+
+/// Trailer appended to Rust code blocks by default
+pub(crate) const DEFAULT_TRAILER: &str = "# Ok::<(), Box<dyn std::error::Error>>(())";
+
+/// Environment variable holding default options for every invocation, in the same `name = value` syntax as the macro
+pub(crate) const DEFAULT_OPTS_VAR: &str = "DOCIFY_DEFAULT_OPTS";
+
+/// Parsed input of the [`docify!`](crate::docify) macro
+pub(crate) struct Input {
+	/// Path to the readme file, relative to Cargo.toml
+	pub(crate) path: LitStr,
+
+	/// Docs URL to replace, if given (it can be left to the manifest's replacements table instead)
+	pub(crate) docs_url: Option<LitStr>,
+
+	/// Replacement for the docs URL, if given
+	pub(crate) replacement: Option<LitStr>,
+
+	/// Additional named options
+	pub(crate) options: Options,
+}
+
+impl Parse for Input {
+	fn parse(input: ParseStream<'_>) -> syn::Result<Self> {
+		let input_span = input.span();
+
+		// Split the arguments into the positional ones and the named options following them, up to any `;`
+		let mut args = Vec::new();
+		while !input.is_empty() && !input.peek(Token![;]) {
+			args.push(input.parse::<Arg>()?);
+			if input.is_empty() || input.peek(Token![;]) {
+				break;
+			}
+			input.parse::<Token![,]>()?;
+		}
+		let mut positional = Vec::new();
+		let mut named = Vec::new();
+		for arg in args {
+			match arg {
+				Arg::Positional(lit) if named.is_empty() => positional.push(lit),
+				Arg::Positional(lit) => {
+					return Err(syn::Error::new_spanned(
+						lit,
+						"positional arguments must come before any options",
+					))
+				}
+				Arg::Named(name, value) => named.push((name, value)),
+			}
+		}
+
+		let (path, docs_url, replacement) = match positional.as_slice() {
+			[path] => (path, None, None),
+			[path, docs_url, replacement] => (path, Some(docs_url.clone()), Some(replacement.clone())),
+			_ => {
+				return Err(syn::Error::new(
+					input_span,
+					r#"expected `"<readme_path>", "<docs_url>", "<replacement_docs_url>"` or just `"<readme_path>"`"#,
+				))
+			}
+		};
+
+		// Options after a `;` come from a macro defined with `docify_config!`, and are treated as defaults
+		let configured = if input.parse::<Option<Token![;]>>()?.is_some() {
+			named_args(Punctuated::<Arg, Token![,]>::parse_terminated(input)?)?
+		} else {
+			Vec::new()
+		};
+
+		// Layer the options so that given options win over configured ones, which win over the default ones
+		let options = layer(layer(default_options(|name| env::var_os(name))?, configured), named);
+
+		Ok(Self {
+			path: path.clone(),
+			docs_url,
+			replacement,
+			options: Options::from_named(options)?,
+		})
+	}
+}
+
+/// Parsed input of the [`docify_config!`](crate::docify_config) macro
+pub(crate) struct Config {
+	/// Name of the macro to define
+	name: Ident,
+
+	/// Docs URL to replace
+	docs_url: LitStr,
+
+	/// Replacement for the docs URL
+	replacement: LitStr,
+
+	/// Named options to pass along to every invocation
+	options: Vec<(Ident, Value)>,
+}
+
+impl Config {
+	/// Builds the definition of a `macro_rules!` macro that invokes [`docify!`](crate::docify) with the configured
+	/// arguments for a given readme path, followed by any further options given to it
+	pub(crate) fn into_macro(self) -> TokenStream {
+		let Self {
+			name,
+			docs_url,
+			replacement,
+			options,
+		} = self;
+		let options = options.into_iter().map(|(name, value)| quote!(#name = #value));
+
+		// The configured options go after a `;`, so that options given at the call site can override them
+		quote! {
+			macro_rules! #name {
+				($path:literal $(, $($option:tt)*)?) => {
+					::pretty_readme::docify!($path, #docs_url, #replacement $(, $($option)*)?; #(#options),*)
+				};
+			}
+		}
+	}
+}
+
+impl Parse for Config {
+	fn parse(input: ParseStream<'_>) -> syn::Result<Self> {
+		let input_span = input.span();
+		let name = input.parse()?;
+
+		let mut docs_url = None;
+		let mut replacement = None;
+		let mut options = Vec::new();
+		while !input.is_empty() {
+			input.parse::<Token![,]>()?;
+			if input.is_empty() {
+				break;
+			}
+
+			let key: Ident = input.parse()?;
+			input.parse::<Token![:]>()?;
+			match key.to_string().as_str() {
+				"docs" => docs_url = Some(input.parse()?),
+				"rel" => replacement = Some(input.parse()?),
+				_ => options.push((key, input.parse()?)),
+			}
+		}
+
+		let (Some(docs_url), Some(replacement)) = (docs_url, replacement) else {
+			return Err(syn::Error::new(
+				input_span,
+				r#"expected `<name>, docs: "<docs_url>", rel: "<replacement_docs_url>"`"#,
+			));
+		};
+
+		// Check the options now, so that mistakes are reported here rather than at each invocation
+		Options::from_named(options.clone())?;
+
+		Ok(Self {
+			name,
+			docs_url,
+			replacement,
+			options,
+		})
+	}
+}
+
+/// Parsed input of the [`docify_with!`](crate::docify_with) macro
+pub(crate) struct Invocation {
+	/// Name of the macro defined by [`docify_config!`](crate::docify_config) to invoke
+	pub(crate) name: Ident,
+
+	/// Arguments to pass along to it
+	pub(crate) args: TokenStream,
+}
+
+impl Parse for Invocation {
+	fn parse(input: ParseStream<'_>) -> syn::Result<Self> {
+		let name = input.parse()?;
+		input.parse::<Token![,]>()?;
+		Ok(Self {
+			name,
+			args: input.parse()?,
+		})
+	}
+}
+
+/// Parses the default options set via [`DEFAULT_OPTS_VAR`], if any, looking it up with the given function
+fn default_options(var: impl Fn(&str) -> Option<OsString>) -> syn::Result<Vec<(Ident, Value)>> {
+	let Some(defaults) = var(DEFAULT_OPTS_VAR)
+		.and_then(|value| value.into_string().ok())
+		.filter(|value| !value.trim().is_empty())
+	else {
+		return Ok(Vec::new());
+	};
+	let invalid = |err: syn::Error| syn::Error::new(Span::call_site(), format!("invalid `{DEFAULT_OPTS_VAR}`: {err}"));
+
+	Punctuated::<Arg, Token![,]>::parse_terminated
+		.parse_str(&defaults)
+		.and_then(named_args)
+		.map_err(invalid)
+}
+
+/// Gets the options from a list of arguments that may only contain `name = value` options
+fn named_args(args: Punctuated<Arg, Token![,]>) -> syn::Result<Vec<(Ident, Value)>> {
+	args.into_iter()
+		.map(|arg| match arg {
+			Arg::Named(name, value) => Ok((name, value)),
+			Arg::Positional(lit) => Err(syn::Error::new_spanned(lit, "only `name = value` options are allowed")),
+		})
+		.collect()
+}
+
+/// Puts one list of options over another, leaving out the options in the lower one that the upper one also has
+fn layer(lower: Vec<(Ident, Value)>, upper: Vec<(Ident, Value)>) -> Vec<(Ident, Value)> {
+	let mut options: Vec<_> = lower
+		.into_iter()
+		.filter(|(lower, _)| !upper.iter().any(|(upper, _)| upper == lower))
+		.collect();
+	options.extend(upper);
+	options
+}
+
+/// Options that alter how the readme is transformed
+#[allow(clippy::struct_excessive_bools)]
+pub(crate) struct Options {
+	/// Trailer text to append to code blocks, keyed by lowercase fence language
+	pub(crate) trailers: BTreeMap<String, String>,
+
+	/// Whether to keep the original URL of rewritten link destinations as the link title
+	pub(crate) keep_original_as_title: bool,
+
+	/// Whether to remove the common leading whitespace from the whole document
+	pub(crate) dedent: bool,
+
+	/// What to do with raw HTML tables
+	pub(crate) html_tables: HtmlTables,
+
+	/// 1-based indices of the code blocks (among those with a trailer language) to leave without a trailer
+	pub(crate) skip_blocks: BTreeSet<usize>,
+
+	/// Whether to leave code blocks inside blockquotes without a trailer
+	pub(crate) skip_blockquoted_code: bool,
+
+	/// Item names reference links may resolve to, if they should be checked
+	pub(crate) known_items: Option<BTreeSet<String>>,
+
+	/// Whether warnings should be turned into errors
+	pub(crate) strict: bool,
+
+	/// Whether to merge all runnable code blocks into a single doctest
+	pub(crate) merge_blocks: bool,
+
+	/// Whether to merge runs of runnable code blocks separated by only blank lines into a single doctest each
+	pub(crate) merge_adjacent_blocks: bool,
+
+	/// Whether to move Rust code blocks into a trailing examples section, each under a heading derived from the
+	/// nearest preceding heading
+	pub(crate) examples_as_sections: bool,
+
+	/// Whether to escape brackets in prose that don't form a link
+	pub(crate) escape_bare_brackets: bool,
+
+	/// What to do with footnotes
+	pub(crate) footnotes: Footnotes,
+
+	/// Number of doctests the transformed readme must contain, if it should be checked
+	pub(crate) expect_doctests: Option<usize>,
+
+	/// Where the transformed readme is meant to be shown
+	pub(crate) target: Target,
+
+	/// Crate name to replace in the readme's prose and the name to replace it with, if any
+	pub(crate) rename: Option<(String, String)>,
+
+	/// Whether to also replace the crate name in paths in Rust code blocks
+	pub(crate) rename_in_code: bool,
+
+	/// Whether to lowercase the languages of code blocks
+	pub(crate) normalize_lang: bool,
+
+	/// Whether to replace the `rs` language of code blocks with `rust`
+	pub(crate) canonical_lang: bool,
+
+	/// Code (usually `use` declarations) to add as hidden lines to the start of every runnable code block, if any
+	pub(crate) prelude: Option<String>,
+
+	/// Whether to allow the prelude's imports to be unused
+	pub(crate) allow_unused_prelude: bool,
+
+	/// Link text whose links should be repointed and their new destination, if any
+	pub(crate) by_text: Option<(String, String)>,
+
+	/// Whether to merge reference definitions with the same destination
+	pub(crate) dedupe_references: bool,
+
+	/// Template to wrap the bodies of code blocks in (containing [`BODY_PLACEHOLDER`] once) instead of appending the
+	/// trailer, if any
+	pub(crate) template: Option<String>,
+
+	/// Whether to remove the readme's title
+	pub(crate) strip_title: bool,
+
+	/// Text of the heading of the only section to keep, if any
+	pub(crate) section: Option<String>,
+
+	/// Whether to append a footer with the crate version to the readme
+	pub(crate) version_footer: bool,
+
+	/// Form that link destinations containing the docs URL are rewritten to
+	pub(crate) relativize: Relativize,
+
+	/// Path of the crate to import referenced [known items](Self::known_items) from in code blocks, if they should be
+	pub(crate) import_items: Option<String>,
+
+	/// Replacement for the docs URL to use instead of the given one in debug builds, if any
+	pub(crate) replacement_debug: Option<LitStr>,
+
+	/// Replacement for the docs URL to use instead of the given one in release builds, if any
+	pub(crate) replacement_release: Option<LitStr>,
+
+	/// Names and JSON representations of the options as given (including defaults), for the report
+	pub(crate) given: Vec<(String, String)>,
+}
+
+impl Options {
+	/// Builds the options from a list of named arguments, starting from the defaults
+	fn from_named(named: Vec<(Ident, Value)>) -> syn::Result<Self> {
+		let mut options = Self::default();
+		let mut seen = HashSet::new();
+
+		for (name, value) in named {
+			let key = name.to_string();
+			if !seen.insert(key.clone()) {
+				return Err(syn::Error::new(
+					name.span(),
+					format!("option `{key}` specified more than once"),
+				));
+			}
+
+			options.given.push((key.clone(), value.to_json()));
+			match key.as_str() {
+				"trailers" => {
+					for pair in value.into_list()? {
+						let (lang, trailer) = pair.into_str_pair()?;
+						options.trailers.insert(codeblocks::trailer_key(&lang), trailer);
+					}
+				}
+				"keep_original_as_title" => options.keep_original_as_title = value.into_bool()?,
+				"dedent" => options.dedent = value.into_bool()?,
+				"html_tables" => options.html_tables = value.into_choice(&HtmlTables::CHOICES)?,
+				"skip_blockquoted_code" => options.skip_blockquoted_code = value.into_bool()?,
+				"skip_blocks" => {
+					for index in value.into_list()? {
+						options.skip_blocks.insert(index.into_index()?);
+					}
+				}
+				"known_items" => {
+					let items = value.into_list()?.into_iter().map(Value::into_str);
+					options.known_items = Some(items.collect::<syn::Result<_>>()?);
+				}
+				"strict" => options.strict = value.into_bool()?,
+				"merge_blocks" => options.merge_blocks = value.into_bool()?,
+				"merge_adjacent_blocks" => options.merge_adjacent_blocks = value.into_bool()?,
+				"examples_as_sections" => options.examples_as_sections = value.into_bool()?,
+				"escape_bare_brackets" => options.escape_bare_brackets = value.into_bool()?,
+				"footnotes" => options.footnotes = value.into_choice(&Footnotes::CHOICES)?,
+				"expect_doctests" => options.expect_doctests = Some(value.into_count()?),
+				"target" => options.target = value.into_choice(&Target::CHOICES)?,
+				"rename" => {
+					let span = value.span();
+					let (old, new) = value.into_str_pair()?;
+					if old.is_empty() {
+						return Err(syn::Error::new(span, "the crate name to replace can't be empty"));
+					}
+					options.rename = Some((old, new));
+				}
+				"rename_in_code" => options.rename_in_code = value.into_bool()?,
+				"normalize_lang" => options.normalize_lang = value.into_bool()?,
+				"canonical_lang" => options.canonical_lang = value.into_bool()?,
+				"prelude" => options.prelude = Some(value.into_str()?),
+				"allow_unused_prelude" => options.allow_unused_prelude = value.into_bool()?,
+				"by_text" => options.by_text = Some(value.into_str_pair()?),
+				"dedupe_references" => options.dedupe_references = value.into_bool()?,
+				"template" => {
+					let span = value.span();
+					let template = value.into_str()?;
+					if template.matches(BODY_PLACEHOLDER).count() != 1 {
+						return Err(syn::Error::new(
+							span,
+							format!("template must contain `{BODY_PLACEHOLDER}` exactly once"),
+						));
+					}
+					options.template = Some(template);
+				}
+				"strip_title" => options.strip_title = value.into_bool()?,
+				"section" => options.section = Some(value.into_str()?),
+				"version_footer" => options.version_footer = value.into_bool()?,
+				"relativize" => options.relativize = value.into_choice(&Relativize::CHOICES)?,
+				"import_items" => options.import_items = Some(value.into_str()?),
+				"replacement_debug" => options.replacement_debug = Some(value.into_lit_str()?),
+				"replacement_release" => options.replacement_release = Some(value.into_lit_str()?),
+				_ => return Err(syn::Error::new(name.span(), format!("unknown option `{key}`"))),
+			}
+		}
+
+		Ok(options)
+	}
+
+	/// Gets the replacement given for the current build profile, if there is one. The profile is determined by the
+	/// `PROFILE` environment variable (`release` or anything else), or by `DEBUG` (`false` or anything else) if that
+	/// isn't set. When neither is set, the profile is unknown and there's no replacement for it. Environment variables
+	/// are looked up with the given function.
+	pub(crate) fn profile_replacement(&self, var: impl Fn(&str) -> Option<OsString>) -> Option<&LitStr> {
+		let release = match var("PROFILE") {
+			Some(profile) => profile == "release",
+			None => matches!(var("DEBUG")?.to_str(), Some("false" | "0")),
+		};
+
+		if release {
+			self.replacement_release.as_ref()
+		} else {
+			self.replacement_debug.as_ref()
+		}
+	}
+}
+
+impl Default for Options {
+	fn default() -> Self {
+		Self {
+			trailers: BTreeMap::from([("rust".to_owned(), DEFAULT_TRAILER.to_owned())]),
+			keep_original_as_title: false,
+			dedent: false,
+			html_tables: HtmlTables::Keep,
+			skip_blocks: BTreeSet::new(),
+			skip_blockquoted_code: false,
+			known_items: None,
+			strict: false,
+			merge_blocks: false,
+			merge_adjacent_blocks: false,
+			examples_as_sections: false,
+			escape_bare_brackets: false,
+			footnotes: Footnotes::Keep,
+			expect_doctests: None,
+			target: Target::Docs,
+			rename: None,
+			rename_in_code: false,
+			normalize_lang: false,
+			canonical_lang: false,
+			prelude: None,
+			allow_unused_prelude: true,
+			by_text: None,
+			dedupe_references: false,
+			template: None,
+			strip_title: false,
+			section: None,
+			version_footer: false,
+			relativize: Relativize::Url,
+			import_items: None,
+			replacement_debug: None,
+			replacement_release: None,
+			given: Vec::new(),
+		}
+	}
+}
+
+/// Handling of raw HTML `<table>` elements
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub(crate) enum HtmlTables {
+	/// Leave tables as-is
+	Keep,
+
+	/// Remove tables entirely
+	Strip,
+}
+
+impl HtmlTables {
+	/// Values of the `html_tables` option, as the strings they're given as
+	const CHOICES: [(&'static str, Self); 2] = [("keep", Self::Keep), ("strip", Self::Strip)];
+}
+
+/// Handling of GFM footnotes (`[^label]`)
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Footnotes {
+	/// Leave footnotes as-is
+	Keep,
+
+	/// Replace references with their footnote's text in parentheses, and remove the definitions
+	Inline,
+
+	/// Remove references and definitions entirely
+	Drop,
+}
+
+impl Footnotes {
+	/// Values of the `footnotes` option, as the strings they're given as
+	const CHOICES: [(&'static str, Self); 3] = [("keep", Self::Keep), ("inline", Self::Inline), ("drop", Self::Drop)];
+}
+
+/// Form that link destinations containing the docs URL are rewritten to
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Relativize {
+	/// Replace the docs URL with its replacement
+	Url,
+
+	/// Turn destinations pointing to item pages into intra-doc link paths (`crate::module::Item`)
+	IntraDoc,
+}
+
+impl Relativize {
+	/// Values of the `relativize` option, as the strings they're given as
+	const CHOICES: [(&'static str, Self); 2] = [("url", Self::Url), ("intra_doc", Self::IntraDoc)];
+}
+
+/// Place the transformed readme is meant to be shown
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Target {
+	/// Rustdoc, which runs code blocks as doctests and resolves relative links against the crate's docs
+	Docs,
+
+	/// GitHub (or anywhere else the readme is shown directly), which shows code blocks as-is
+	Github,
+}
+
+impl Target {
+	/// Values of the `target` option, as the strings they're given as
+	const CHOICES: [(&'static str, Self); 2] = [("docs", Self::Docs), ("github", Self::Github)];
+}
+
+/// Single argument given to the macro
+enum Arg {
+	/// Plain string literal
+	Positional(LitStr),
+
+	/// `name = value` option
+	Named(Ident, Value),
+}
+
+impl Parse for Arg {
+	fn parse(input: ParseStream<'_>) -> syn::Result<Self> {
+		if input.peek(Ident) && input.peek2(Token![=]) {
+			let name = input.parse()?;
+			input.parse::<Token![=]>()?;
+			Ok(Self::Named(name, input.parse()?))
+		} else {
+			Ok(Self::Positional(input.parse()?))
+		}
+	}
+}
+
+/// Value given for a named option
+#[derive(Clone)]
+enum Value {
+	/// Literal (string, boolean, integer, etc.)
+	Lit(Lit),
+
+	/// Bracketed list of values (`[a, b]`)
+	List(Span, Vec<Value>),
+
+	/// Parenthesized tuple of values (`(a, b)`)
+	Tuple(Span, Vec<Value>),
+}
+
+impl Value {
+	/// Gets the span of the value for use in errors
+	fn span(&self) -> Span {
+		match self {
+			Self::Lit(lit) => lit.span(),
+			Self::List(span, _) | Self::Tuple(span, _) => *span,
+		}
+	}
+
+	/// Renders the value as JSON
+	fn to_json(&self) -> String {
+		match self {
+			Self::Lit(Lit::Str(lit)) => report::json_string(&lit.value()),
+			Self::Lit(Lit::Bool(lit)) => lit.value.to_string(),
+			Self::Lit(Lit::Int(lit)) => lit.base10_digits().to_owned(),
+			Self::Lit(lit) => report::json_string(&lit.to_token_stream().to_string()),
+			Self::List(_, values) | Self::Tuple(_, values) => {
+				format!("[{}]", values.iter().map(Self::to_json).collect::<Vec<_>>().join(", "))
+			}
+		}
+	}
+
+	/// Consumes the value as a string
+	fn into_str(self) -> syn::Result<String> {
+		self.into_lit_str().map(|lit| lit.value())
+	}
+
+	/// Consumes the value as a string literal, for when its span is still needed
+	fn into_lit_str(self) -> syn::Result<LitStr> {
+		match self {
+			Self::Lit(Lit::Str(lit)) => Ok(lit),
+			_ => Err(syn::Error::new(self.span(), "expected a string")),
+		}
+	}
+
+	/// Consumes the value as a string that must be the name of one of the given choices, getting the chosen value
+	fn into_choice<T: Copy>(self, choices: &[(&str, T)]) -> syn::Result<T> {
+		let span = self.span();
+		let name = self.into_str()?;
+		choices
+			.iter()
+			.find(|(choice, _)| *choice == name)
+			.map(|&(_, value)| value)
+			.ok_or_else(|| {
+				let names = choices
+					.iter()
+					.map(|(choice, _)| format!(r#"`"{choice}"`"#))
+					.collect::<Vec<_>>();
+				let expected = match names.as_slice() {
+					[first, second] => format!("{first} or {second}"),
+					[rest @ .., last] => format!("{}, or {last}", rest.join(", ")),
+					[] => String::new(),
+				};
+				syn::Error::new(span, format!("expected {expected}"))
+			})
+	}
+
+	/// Consumes the value as a boolean
+	fn into_bool(self) -> syn::Result<bool> {
+		match self {
+			Self::Lit(Lit::Bool(lit)) => Ok(lit.value),
+			_ => Err(syn::Error::new(self.span(), "expected a boolean")),
+		}
+	}
+
+	/// Consumes the value as a 1-based index
+	fn into_index(self) -> syn::Result<usize> {
+		match self {
+			Self::Lit(Lit::Int(lit)) => match lit.base10_parse()? {
+				0 => Err(syn::Error::new(lit.span(), "indices start at 1")),
+				index => Ok(index),
+			},
+			_ => Err(syn::Error::new(self.span(), "expected an integer")),
+		}
+	}
+
+	/// Consumes the value as a non-negative count
+	fn into_count(self) -> syn::Result<usize> {
+		match self {
+			Self::Lit(Lit::Int(lit)) => lit.base10_parse(),
+			_ => Err(syn::Error::new(self.span(), "expected an integer")),
+		}
+	}
+
+	/// Consumes the value as a list of values
+	fn into_list(self) -> syn::Result<Vec<Self>> {
+		match self {
+			Self::List(_, values) => Ok(values),
+			_ => Err(syn::Error::new(self.span(), "expected a list (`[...]`)")),
+		}
+	}
+
+	/// Consumes the value as a tuple of two strings
+	fn into_str_pair(self) -> syn::Result<(String, String)> {
+		match self {
+			Self::Tuple(span, values) => match <[Self; 2]>::try_from(values) {
+				Ok([first, second]) => Ok((first.into_str()?, second.into_str()?)),
+				Err(_) => Err(syn::Error::new(
+					span,
+					r#"expected a pair of strings (`("...", "...")`)"#,
+				)),
+			},
+			_ => Err(syn::Error::new(
+				self.span(),
+				r#"expected a pair of strings (`("...", "...")`)"#,
+			)),
+		}
+	}
+}
+
+impl ToTokens for Value {
+	fn to_tokens(&self, tokens: &mut TokenStream) {
+		let (delimiter, span, values) = match self {
+			Self::Lit(lit) => return lit.to_tokens(tokens),
+			Self::List(span, values) => (Delimiter::Bracket, span, values),
+			Self::Tuple(span, values) => (Delimiter::Parenthesis, span, values),
+		};
+
+		let mut group = Group::new(delimiter, quote!(#(#values),*));
+		group.set_span(*span);
+		tokens.append(group);
+	}
+}
+
+impl Parse for Value {
+	fn parse(input: ParseStream<'_>) -> syn::Result<Self> {
+		let content;
+		if input.peek(token::Bracket) {
+			let bracket = bracketed!(content in input);
+			let values = Punctuated::<Self, Token![,]>::parse_terminated(&content)?;
+			Ok(Self::List(bracket.span.join(), values.into_iter().collect()))
+		} else if input.peek(token::Paren) {
+			let paren = parenthesized!(content in input);
+			let values = Punctuated::<Self, Token![,]>::parse_terminated(&content)?;
+			Ok(Self::Tuple(paren.span.join(), values.into_iter().collect()))
+		} else {
+			Ok(Self::Lit(input.parse()?))
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	//! Tests for parsing the macros' input
+
+	use super::*;
+	use crate::test_util::vars;
+
+	/// Parses the input of the [`docify!`](crate::docify) macro
+	fn parse(input: &str) -> syn::Result<Input> {
+		syn::parse_str(input)
+	}
+
+	/// Options given at the call site of a configured macro override the configured ones
+	#[test]
+	fn given_options_override_configured_ones() {
+		let input = parse(concat!(
+			r#""README.md", "https://docs.rs/some_crate/", "./", strip_title = false;"#,
+			"strip_title = true, dedent = true",
+		))
+		.expect("input should parse");
+		assert!(!input.options.strip_title);
+		assert!(input.options.dedent);
+	}
+
+	/// A configuration defines a macro passing its docs URL and replacement to `docify!`, with its options after the
+	/// ones given at the call site
+	#[test]
+	fn config_defines_macro() {
+		let config = syn::parse_str::<Config>(
+			r#"readme_docs, docs: "https://docs.rs/some_crate/", rel: "./", strip_title: true, section: "Usage","#,
+		)
+		.expect("config should parse");
+		assert_eq!(
+			config.into_macro().to_string(),
+			quote! {
+				macro_rules! readme_docs {
+					($path:literal $(, $($option:tt)*)?) => {
+						::pretty_readme::docify!(
+							$path, "https://docs.rs/some_crate/", "./" $(, $($option)*)?;
+							strip_title = true, section = "Usage"
+						)
+					};
+				}
+			}
+			.to_string()
+		);
+	}
+
+	/// The replacement for the build profile comes from `PROFILE`, or `DEBUG` when that isn't set
+	#[test]
+	fn profile_replacement_follows_profile() {
+		let input = parse(concat!(
+			r#""README.md", "https://docs.rs/some_crate/", "./", "#,
+			r#"replacement_debug = "target/doc/", replacement_release = "https://docs.rs/some_crate/latest/""#,
+		))
+		.expect("input should parse");
+		let replacement = |env| input.options.profile_replacement(vars(env)).map(LitStr::value);
+
+		assert_eq!(
+			replacement(&[("PROFILE", "release"), ("DEBUG", "true")]).unwrap(),
+			"https://docs.rs/some_crate/latest/"
+		);
+		assert_eq!(
+			replacement(&[("PROFILE", "debug"), ("DEBUG", "false")]).unwrap(),
+			"target/doc/"
+		);
+		assert_eq!(
+			replacement(&[("DEBUG", "false")]).unwrap(),
+			"https://docs.rs/some_crate/latest/"
+		);
+		assert_eq!(
+			replacement(&[("DEBUG", "0")]).unwrap(),
+			"https://docs.rs/some_crate/latest/"
+		);
+		assert_eq!(replacement(&[("DEBUG", "true")]).unwrap(), "target/doc/");
+		assert_eq!(replacement(&[]), None);
+	}
+
+	/// Default options from the environment apply unless they're given explicitly, and must be valid options
+	#[test]
+	fn default_options_are_overridden() {
+		let env = [(DEFAULT_OPTS_VAR, "strip_title = false, dedent = true")];
+		let named = Punctuated::<Arg, Token![,]>::parse_terminated
+			.parse_str("strip_title = true")
+			.and_then(named_args)
+			.expect("options should parse");
+		let options = Options::from_named(layer(default_options(vars(&env)).unwrap(), named)).unwrap();
+		assert!(options.strip_title);
+		assert!(options.dedent);
+
+		assert!(default_options(vars(&[(DEFAULT_OPTS_VAR, " ")])).unwrap().is_empty());
+		assert!(default_options(vars(&[])).unwrap().is_empty());
+
+		let err = default_options(vars(&[(DEFAULT_OPTS_VAR, r#""README.md""#)]))
+			.err()
+			.expect("positional defaults should be rejected");
+		assert_eq!(
+			err.to_string(),
+			format!("invalid `{DEFAULT_OPTS_VAR}`: only `name = value` options are allowed")
+		);
+	}
+
+	/// Options may still only be given once in each place
+	#[test]
+	fn duplicate_options_are_rejected() {
+		let err = parse(r#""README.md", "https://docs.rs/some_crate/", "./", dedent = true, dedent = false"#)
+			.err()
+			.expect("duplicate options should be rejected");
+		assert_eq!(err.to_string(), "option `dedent` specified more than once");
+	}
+}
@@ -0,0 +1,196 @@
+//! Machine-readable report of what an invocation did to the readme, for tooling and CI dashboards
+
+use std::fmt::Write;
+
+/// Environment variable that, when set, holds the path of a JSON file to write the report of each invocation to
+pub(crate) const REPORT_VAR: &str = "DOCIFY_REPORT";
+
+/// What happened to a code block
+pub(crate) enum BlockStatus {
+	/// The trailer was appended
+	Trailer,
+
+	/// The body was wrapped in a function returning an `Option`
+	OptionWrapper,
+
+	/// The body was wrapped in a function returning a `Result`, since it ends with an `Ok(...)` expression without a
+	/// type annotation
+	ResultWrapper,
+
+	/// The block was left without a trailer, for the given reason
+	Skipped(String),
+
+	/// The block was merged into the block with the given 1-based index
+	MergedInto(usize),
+
+	/// Other blocks were merged into this one
+	MergeTarget,
+}
+
+/// Report of the transformation of a readme
+#[derive(Default)]
+pub(crate) struct Report {
+	/// Code blocks found (among those with a trailer language), as their 1-based index, info string, and status
+	blocks: Vec<(usize, String, BlockStatus)>,
+
+	/// Docs URLs and their replacements, with the number of occurrences of each docs URL that were replaced
+	replacements: Vec<(String, String, usize)>,
+}
+
+impl Report {
+	/// Records what happened to the code block with the given 1-based index
+	pub(crate) fn block(&mut self, index: usize, info: &str, status: BlockStatus) {
+		self.blocks.push((index, info.to_owned(), status));
+	}
+
+	/// Records the number of occurrences of a docs URL that were replaced with the given replacement
+	pub(crate) fn replacement(&mut self, docs_url: &str, replacement: &str, count: usize) {
+		self.replacements
+			.push((docs_url.to_owned(), replacement.to_owned(), count));
+	}
+
+	/// Consumes the report, rendering it as a JSON document, including the invocation's readme path and options (as
+	/// given, in JSON form)
+	pub(crate) fn into_json(mut self, readme: &str, options: &[(String, String)]) -> String {
+		self.blocks.sort_by_key(|(index, ..)| *index);
+		let fixed = self
+			.blocks
+			.iter()
+			.filter(|(.., status)| {
+				matches!(
+					status,
+					BlockStatus::Trailer
+						| BlockStatus::OptionWrapper
+						| BlockStatus::ResultWrapper
+						| BlockStatus::MergeTarget
+				)
+			})
+			.count();
+		let skipped = self
+			.blocks
+			.iter()
+			.filter(|(.., status)| matches!(status, BlockStatus::Skipped(_)))
+			.count();
+
+		let mut json = String::from("{\n");
+		let _ = writeln!(json, "\t\"readme\": {},", json_string(readme));
+		let options = options
+			.iter()
+			.map(|(name, value)| format!("{}: {value}", json_string(name)))
+			.collect::<Vec<_>>();
+		let _ = writeln!(json, "\t\"options\": {{{}}},", options.join(", "));
+		let _ = writeln!(
+			json,
+			"\t\"blocks\": {{\"found\": {}, \"fixed\": {fixed}, \"skipped\": {skipped}, \"details\": [",
+			self.blocks.len()
+		);
+		for (idx, (index, info, status)) in self.blocks.iter().enumerate() {
+			let status = match status {
+				BlockStatus::Trailer => r#""status": "trailer""#.to_owned(),
+				BlockStatus::OptionWrapper => r#""status": "option""#.to_owned(),
+				BlockStatus::ResultWrapper => r#""status": "result""#.to_owned(),
+				BlockStatus::Skipped(reason) => format!(r#""status": "skipped", "reason": {}"#, json_string(reason)),
+				BlockStatus::MergedInto(target) => format!(r#""status": "merged", "into": {target}"#),
+				BlockStatus::MergeTarget => r#""status": "merge_target""#.to_owned(),
+			};
+			let separator = if idx.saturating_add(1) < self.blocks.len() {
+				","
+			} else {
+				""
+			};
+			let _ = writeln!(
+				json,
+				"\t\t{{\"index\": {index}, \"info\": {}, {status}}}{separator}",
+				json_string(info)
+			);
+		}
+		json.push_str("\t]},\n");
+		let replacements = self
+			.replacements
+			.iter()
+			.map(|(docs_url, replacement, count)| {
+				format!(
+					"{{\"docs_url\": {}, \"replacement\": {}, \"count\": {count}}}",
+					json_string(docs_url),
+					json_string(replacement)
+				)
+			})
+			.collect::<Vec<_>>();
+		let _ = writeln!(json, "\t\"replacements\": [{}]", replacements.join(", "));
+		json.push_str("}\n");
+		json
+	}
+}
+
+/// Renders a string as a JSON string literal
+pub(crate) fn json_string(value: &str) -> String {
+	let mut json = String::with_capacity(value.len().saturating_add(2));
+	json.push('"');
+	for c in value.chars() {
+		match c {
+			'"' => json.push_str(r#"\""#),
+			'\\' => json.push_str(r"\\"),
+			'\n' => json.push_str(r"\n"),
+			'\r' => json.push_str(r"\r"),
+			'\t' => json.push_str(r"\t"),
+			c if c.is_control() => {
+				let _ = write!(json, "\\u{:04x}", u32::from(c));
+			}
+			c => json.push(c),
+		}
+	}
+	json.push('"');
+	json
+}
+
+#[cfg(test)]
+mod tests {
+	//! Tests for rendering reports
+
+	use super::*;
+
+	/// The report lists the blocks in order of their index with their counts summarized, and each replacement with its
+	/// own count
+	#[test]
+	fn into_json_renders_report() {
+		let mut report = Report::default();
+		report.block(3, "rust", BlockStatus::MergeTarget);
+		report.block(
+			1,
+			"rust,ignore",
+			BlockStatus::Skipped("has the `ignore` attribute".to_owned()),
+		);
+		report.block(2, "rust", BlockStatus::MergedInto(3));
+		report.block(4, "rs", BlockStatus::Trailer);
+		report.replacement("https://docs.rs/some_crate/", "./", 2);
+		report.replacement("https://docs.rs/other_crate/", "https://example.com/", 0);
+
+		let options = [("strip_title".to_owned(), "true".to_owned())];
+		assert_eq!(
+			report.into_json("README.md", &options),
+			concat!(
+				"{\n",
+				"\t\"readme\": \"README.md\",\n",
+				"\t\"options\": {\"strip_title\": true},\n",
+				"\t\"blocks\": {\"found\": 4, \"fixed\": 2, \"skipped\": 1, \"details\": [\n",
+				"\t\t{\"index\": 1, \"info\": \"rust,ignore\", \"status\": \"skipped\", ",
+				"\"reason\": \"has the `ignore` attribute\"},\n",
+				"\t\t{\"index\": 2, \"info\": \"rust\", \"status\": \"merged\", \"into\": 3},\n",
+				"\t\t{\"index\": 3, \"info\": \"rust\", \"status\": \"merge_target\"},\n",
+				"\t\t{\"index\": 4, \"info\": \"rs\", \"status\": \"trailer\"}\n",
+				"\t]},\n",
+				"\t\"replacements\": [{\"docs_url\": \"https://docs.rs/some_crate/\", ",
+				"\"replacement\": \"./\", \"count\": 2}, ",
+				"{\"docs_url\": \"https://docs.rs/other_crate/\", ",
+				"\"replacement\": \"https://example.com/\", \"count\": 0}]\n",
+				"}\n",
+			)
+		);
+	}
+
+	/// Quotes, backslashes, and control characters are escaped in JSON strings
+	#[test]
+	fn json_string_escapes() {
+		assert_eq!(json_string("a \"b\" \\ c\n\t\u{1}"), r#""a \"b\" \\ c\n\t\u0001""#);
+	}
+}
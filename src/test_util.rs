@@ -0,0 +1,12 @@
+//! Helpers shared by the unit tests
+
+use std::ffi::OsString;
+
+/// Looks up variables from a fixed list instead of the process environment
+pub(crate) fn vars<'a>(vars: &'a [(&str, &str)]) -> impl Fn(&str) -> Option<OsString> + 'a {
+	|name| {
+		vars.iter()
+			.find(|(var, _)| *var == name)
+			.map(|(_, value)| OsString::from(value))
+	}
+}
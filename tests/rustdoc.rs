@@ -0,0 +1,55 @@
+//! Checks that transformed readmes actually pass when rustdoc runs their code blocks as doctests
+
+use std::{env, fs, process};
+
+/// Runs rustdoc's doctests on the given markdown (written to a temporary file with the given name), panicking with
+/// rustdoc's output if any of them fail
+fn assert_doctests_pass(name: &str, markdown: &str) {
+	let path = env::temp_dir().join(format!("pretty_readme_{}_{name}.md", process::id()));
+	fs::write(&path, markdown).expect("unable to write markdown to test");
+	let output = process::Command::new(env::var_os("RUSTDOC").unwrap_or_else(|| "rustdoc".into()))
+		.args(["--test", "--edition", "2021"])
+		.arg(&path)
+		.output()
+		.expect("unable to run rustdoc");
+	let _ = fs::remove_file(&path);
+
+	assert!(
+		output.status.success(),
+		"doctests failed:\n{}{}",
+		String::from_utf8_lossy(&output.stdout),
+		String::from_utf8_lossy(&output.stderr)
+	);
+}
+
+/// Blocks that end with an `Ok(...)` expression, with or without a type annotation, compile and run
+#[test]
+fn returns_ok() {
+	assert_doctests_pass(
+		"returns_ok",
+		pretty_readme::docify!("tests/fixtures/returns_ok.md", "https://docs.rs/some_crate/", "./"),
+	);
+}
+
+/// With `merge_blocks`, a later block can use a binding from an earlier one
+#[test]
+fn merge_blocks() {
+	assert_doctests_pass(
+		"merge_blocks",
+		pretty_readme::docify!(
+			"tests/fixtures/merged.md",
+			"https://docs.rs/some_crate/",
+			"./",
+			merge_blocks = true
+		),
+	);
+}
+
+/// Blocks with the option directive use `?` on an `Option` inside a function returning one
+#[test]
+fn option_directive() {
+	assert_doctests_pass(
+		"option_directive",
+		pretty_readme::docify!("tests/fixtures/option.md", "https://docs.rs/some_crate/", "./"),
+	);
+}